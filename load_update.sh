@@ -2,8 +2,6 @@
 # expectations:
 # - the rsync logs have been mounted at /logs
 # - the rsync results have been mounted at /bulk/ifremer
-# - the logs folder from last night is the most recent, and contains a file named rsyncupdates which lists every netcdf file touched by the rsync
+# - the logs folder from last night is the most recent under /logs/ifremer, and contains a file named rsyncupdates which lists every netcdf file touched by the rsync
 
-updaterecord=$(ls -ltd -- /logs/ifremer/* | head -n 1 | awk '{print $NF}')
-cat ${updaterecord}/rsyncupdates | sed 's|^|target/release/nc2mongo |' > update.sh
-source update.sh
\ No newline at end of file
+target/release/nc2mongo update --log-root /logs/ifremer
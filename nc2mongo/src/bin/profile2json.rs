@@ -0,0 +1,17 @@
+// print the documents nc2mongo would store for each profile in the given netCDF
+// files, one JSON object per line; no database needed
+use nc2mongo::{parse_profile_file, Error};
+use std::io::{self, Write};
+use std::path::Path;
+
+fn main() -> Result<(), Error> {
+    let mut out = io::BufWriter::new(io::stdout().lock());
+    for filename in std::env::args().skip(1) {
+        for profile in parse_profile_file(Path::new(&filename))? {
+            serde_json::to_writer(&mut out, &profile)?;
+            out.write_all(b"\n")?;
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
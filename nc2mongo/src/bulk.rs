@@ -0,0 +1,153 @@
+// buffered, unordered inserts for rebuilding the database from scratch
+use std::collections::HashSet;
+use mongodb::Collection;
+use mongodb::error::ErrorKind;
+use mongodb::options::InsertManyOptions;
+use serde::Serialize;
+use crate::error::Error;
+use crate::retry::RetryPolicy;
+use crate::schema::{DataSchema, MapSchema, ProfileDoc};
+
+// server error code for a duplicate key
+pub const DUPLICATE_KEY: i32 = 11000;
+
+#[derive(Debug)]
+pub struct RejectedDocument {
+    pub collection: String,
+    pub id: String,
+    pub source_file: String,
+    pub message: String,
+}
+
+// collects documents and writes them with insert_many once batch_size have
+// accumulated; assumes the target collections start out empty, so nothing is
+// deleted or upserted. Per-document write errors, and every file of a batch
+// that could not be written at all, are recorded instead of aborting the run.
+pub struct BulkWriter {
+    argo: Collection<DataSchema>,
+    argo_search: Collection<MapSchema>,
+    batch_size: usize,
+    retry: RetryPolicy,
+    data: Vec<DataSchema>,
+    maps: Vec<MapSchema>,
+    pending_files: Vec<String>,
+    pub inserted: usize,
+    pub duplicates: Vec<RejectedDocument>,
+    pub rejected: Vec<RejectedDocument>,
+}
+
+impl BulkWriter {
+    pub fn new(argo: Collection<DataSchema>, argo_search: Collection<MapSchema>, batch_size: usize, retry: RetryPolicy) -> Self {
+        BulkWriter {
+            argo,
+            argo_search,
+            batch_size: batch_size.max(1),
+            retry,
+            data: Vec::new(),
+            maps: Vec::new(),
+            pending_files: Vec::new(),
+            inserted: 0,
+            duplicates: Vec::new(),
+            rejected: Vec::new(),
+        }
+    }
+
+    // buffer every profile from one source file, flushing if the batch is full;
+    // returns the source files committed by that flush, if any
+    pub async fn push(&mut self, source_file: String, profiles: Vec<ProfileDoc>) -> Result<Vec<String>, Error> {
+        for profile in profiles {
+            self.data.push(profile.data);
+            self.maps.push(profile.search);
+        }
+        self.pending_files.push(source_file);
+        if self.data.len() >= self.batch_size {
+            return self.flush().await;
+        }
+        Ok(Vec::new())
+    }
+
+    // write everything buffered, returning the source files whose documents all made it in.
+    // The batch leaves the buffer whatever happens, so the files of a batch that failed are
+    // never returned as committed by a later flush; instead each of them is recorded as
+    // rejected, since there is no telling which of their documents got in.
+    pub async fn flush(&mut self) -> Result<Vec<String>, Error> {
+        let rejected_before = self.rejected.len();
+        let data = std::mem::take(&mut self.data);
+        let maps = std::mem::take(&mut self.maps);
+        let mut committed = std::mem::take(&mut self.pending_files);
+
+        let data_keys: Vec<(String, String)> = data.iter().map(|d| (d._id.clone(), d.source_file.clone())).collect();
+        let result = match insert_batch(&self.argo, data, &data_keys, self.retry, &mut self.duplicates, &mut self.rejected).await {
+            Ok(inserted) => {
+                self.inserted += inserted;
+                let map_keys: Vec<(String, String)> = maps.iter().map(|m| (m._id.clone(), m.source_file.clone())).collect();
+                insert_batch(&self.argo_search, maps, &map_keys, self.retry, &mut self.duplicates, &mut self.rejected)
+                    .await
+                    .map_err(|e| (self.argo_search.name().to_string(), e))
+            }
+            Err(e) => Err((self.argo.name().to_string(), e)),
+        };
+        if let Err((collection, e)) = result {
+            eprintln!("Failed to insert a batch of {} files into {}: {}", committed.len(), collection, e);
+            for source_file in committed {
+                self.rejected.push(RejectedDocument {
+                    collection: collection.clone(),
+                    id: format!("the documents of {}", source_file),
+                    source_file,
+                    message: e.to_string(),
+                });
+            }
+            return Ok(Vec::new());
+        }
+
+        let incomplete: HashSet<&String> = self.rejected[rejected_before..].iter().map(|r| &r.source_file).collect();
+        committed.retain(|source_file| !incomplete.contains(source_file));
+        Ok(committed)
+    }
+}
+
+// insert docs unordered, sorting any per-document failures into duplicates or rejected;
+// keys holds the (_id, source_file) of each doc. Returns the number of documents actually written.
+// A retried batch may find some of its documents already in, which then show up as duplicates.
+async fn insert_batch<T: Serialize>(
+    collection: &Collection<T>,
+    docs: Vec<T>,
+    keys: &[(String, String)],
+    retry: RetryPolicy,
+    duplicates: &mut Vec<RejectedDocument>,
+    rejected: &mut Vec<RejectedDocument>,
+) -> Result<usize, Error> {
+    if docs.is_empty() {
+        return Ok(0);
+    }
+    let options = InsertManyOptions::builder().ordered(false).build();
+    let what = format!("Inserting {} documents into {}", docs.len(), collection.name());
+    let result = retry.run(&what, || async { Ok(collection.insert_many(docs.iter(), options.clone()).await?) }).await;
+    match result {
+        Ok(result) => Ok(result.inserted_ids.len()),
+        Err(e) => {
+            let write_errors = match &e {
+                Error::Mongo(err) => match &*err.kind {
+                    ErrorKind::BulkWrite(failure) if failure.write_concern_error.is_none() => failure.write_errors.clone().unwrap_or_default(),
+                    _ => return Err(e),
+                },
+                _ => return Err(e),
+            };
+            for err in &write_errors {
+                let (id, source_file) = keys.get(err.index).cloned().unwrap_or_default();
+                let rejection = RejectedDocument {
+                    collection: collection.name().to_string(),
+                    id,
+                    source_file,
+                    message: err.message.clone(),
+                };
+                if err.code == DUPLICATE_KEY {
+                    duplicates.push(rejection);
+                } else {
+                    rejected.push(rejection);
+                }
+            }
+            Ok(keys.len() - write_errors.len())
+        }
+    }
+}
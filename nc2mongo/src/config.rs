@@ -0,0 +1,157 @@
+// deployment settings: where documents go and how local paths map to upstream URLs. Read
+// from an optional TOML file, then overridden by environment variables, so staging and
+// production can share one binary and one image.
+use std::env;
+use std::fs;
+use std::path::Path;
+use mongodb::{Client, Database};
+use serde::Deserialize;
+use crate::dns::{Dns, Resolver};
+use crate::error::Error;
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    // connection string; MONGODB_URI
+    pub mongodb_uri: Option<String>,
+    // NC2MONGO_DATABASE
+    pub database: String,
+    pub collections: Collections,
+    pub source: Source,
+    pub dns: Dns,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Collections {
+    // NC2MONGO_ARGO_COLLECTION
+    pub argo: String,
+    // NC2MONGO_ARGO_SEARCH_COLLECTION
+    pub argo_search: String,
+    // synthetic (S-file) profiles; NC2MONGO_ARGO_SYNTHETIC_COLLECTION
+    pub argo_synthetic: String,
+    // NC2MONGO_ARGO_SYNTHETIC_SEARCH_COLLECTION
+    pub argo_synthetic_search: String,
+    // core and B-file profiles merged by cycle; NC2MONGO_ARGO_MERGED_COLLECTION
+    pub argo_merged: String,
+    // NC2MONGO_ARGO_MERGED_SEARCH_COLLECTION
+    pub argo_merged_search: String,
+    // NC2MONGO_ARGO_HISTORY_COLLECTION
+    pub argo_history: String,
+    // NC2MONGO_RUNS_COLLECTION
+    pub runs: String,
+    // NC2MONGO_INGEST_FAILURES_COLLECTION
+    pub ingest_failures: String,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Source {
+    // upstream URL of the DAC directory; NC2MONGO_SOURCE_URL_PREFIX
+    pub url_prefix: String,
+    // a local path's DAC-relative part is whatever follows this; NC2MONGO_SOURCE_PATH_MARKER
+    pub path_marker: String,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            mongodb_uri: None,
+            database: "argo".to_string(),
+            collections: Collections::default(),
+            source: Source::default(),
+            dns: Dns::default(),
+        }
+    }
+}
+
+impl Default for Collections {
+    fn default() -> Self {
+        Collections {
+            argo: "argo".to_string(),
+            argo_search: "argo_search".to_string(),
+            argo_synthetic: "argo_synthetic".to_string(),
+            argo_synthetic_search: "argo_synthetic_search".to_string(),
+            argo_merged: "argo_merged".to_string(),
+            argo_merged_search: "argo_merged_search".to_string(),
+            argo_history: "argo_history".to_string(),
+            runs: "runs".to_string(),
+            ingest_failures: "ingest_failures".to_string(),
+        }
+    }
+}
+
+impl Default for Source {
+    fn default() -> Self {
+        Source {
+            url_prefix: "ftp://ftp.ifremer.fr/ifremer/argo/dac/".to_string(),
+            path_marker: "ifremer/".to_string(),
+        }
+    }
+}
+
+impl Config {
+    // read path if given, else the file named by NC2MONGO_CONFIG if that is set, else
+    // start from the defaults; then apply environment overrides
+    pub fn load(path: Option<&Path>) -> Result<Config, Error> {
+        let path = path.map(|p| p.to_path_buf()).or_else(|| env::var_os("NC2MONGO_CONFIG").map(Into::into));
+        let mut config = match path {
+            Some(path) => {
+                let text = fs::read_to_string(&path)
+                    .map_err(|e| Error::Setup(format!("could not read config file {}: {}", path.display(), e)))?;
+                toml::from_str(&text)
+                    .map_err(|e| Error::Setup(format!("could not parse config file {}: {}", path.display(), e)))?
+            }
+            None => Config::default(),
+        };
+
+        if let Ok(uri) = env::var("MONGODB_URI") {
+            config.mongodb_uri = Some(uri);
+        }
+        override_from_env(&mut config.database, "NC2MONGO_DATABASE");
+        override_from_env(&mut config.collections.argo, "NC2MONGO_ARGO_COLLECTION");
+        override_from_env(&mut config.collections.argo_search, "NC2MONGO_ARGO_SEARCH_COLLECTION");
+        override_from_env(&mut config.collections.argo_synthetic, "NC2MONGO_ARGO_SYNTHETIC_COLLECTION");
+        override_from_env(&mut config.collections.argo_synthetic_search, "NC2MONGO_ARGO_SYNTHETIC_SEARCH_COLLECTION");
+        override_from_env(&mut config.collections.argo_merged, "NC2MONGO_ARGO_MERGED_COLLECTION");
+        override_from_env(&mut config.collections.argo_merged_search, "NC2MONGO_ARGO_MERGED_SEARCH_COLLECTION");
+        override_from_env(&mut config.collections.argo_history, "NC2MONGO_ARGO_HISTORY_COLLECTION");
+        override_from_env(&mut config.collections.runs, "NC2MONGO_RUNS_COLLECTION");
+        override_from_env(&mut config.collections.ingest_failures, "NC2MONGO_INGEST_FAILURES_COLLECTION");
+        override_from_env(&mut config.source.url_prefix, "NC2MONGO_SOURCE_URL_PREFIX");
+        override_from_env(&mut config.source.path_marker, "NC2MONGO_SOURCE_PATH_MARKER");
+        if let Ok(name) = env::var("NC2MONGO_DNS_RESOLVER") {
+            config.dns.resolver = Resolver::parse(&name)
+                .ok_or_else(|| Error::Setup(format!("unknown NC2MONGO_DNS_RESOLVER {}; expected system, cloudflare, google, quad9 or custom", name)))?;
+        }
+        if let Ok(list) = env::var("NC2MONGO_DNS_NAMESERVERS") {
+            config.dns.nameservers = list.split(',')
+                .map(|address| address.trim().parse().map_err(|_| Error::Setup(format!("bad address {} in NC2MONGO_DNS_NAMESERVERS", address))))
+                .collect::<Result<_, _>>()?;
+        }
+        if !config.dns.nameservers.is_empty() && config.dns.resolver != Resolver::Custom {
+            return Err(Error::Setup("DNS nameservers are only used by the custom resolver; set resolver = \"custom\" as well".to_string()));
+        }
+        Ok(config)
+    }
+
+    pub fn database(&self, client: &Client) -> Database {
+        client.database(&self.database)
+    }
+}
+
+impl Source {
+    // construct link to upstream netcdf file
+    pub fn url(&self, filename: &str) -> Result<String, Error> {
+        match filename.split_once(&self.path_marker) {
+            Some((_, path)) => Ok(format!("{}{}", self.url_prefix, path)),
+            None => Err(Error::Setup(format!("{} is not under a {} mirror directory", filename, self.path_marker))),
+        }
+    }
+}
+
+fn override_from_env(setting: &mut String, var: &str) {
+    if let Ok(value) = env::var(var) {
+        *setting = value;
+    }
+}
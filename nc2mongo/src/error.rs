@@ -0,0 +1,74 @@
+// everything that can go wrong ingesting a file, so callers can decide what each case means
+use std::io;
+use thiserror::Error;
+use tokio::task::JoinError;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("missing dimension {0}")]
+    MissingDimension(String),
+
+    #[error("missing variable {0}")]
+    MissingVariable(String),
+
+    #[error("missing attribute {attribute} on {variable}")]
+    MissingAttribute { variable: String, attribute: String },
+
+    #[error("attribute {attribute} on {variable} is not a string")]
+    BadAttributeType { variable: String, attribute: String },
+
+    #[error("could not decode {variable}: {reason}")]
+    StringDecode { variable: String, reason: String },
+
+    // a B-file whose profiles can't be lined up with those of its core file
+    #[error("cannot merge: {0}")]
+    Merge(String),
+
+    #[error("PRES of profile {profile} differs between the core file and the B-file: {reason}")]
+    PresConflict { profile: String, reason: String },
+
+    #[error("netCDF error: {0}")]
+    NetCdf(#[from] netcdf::Error),
+
+    #[error("MongoDB error: {0}")]
+    Mongo(#[from] mongodb::error::Error),
+
+    #[error("could not serialize document: {0}")]
+    Serialize(#[from] bson::ser::Error),
+
+    #[error("could not write JSON: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+
+    // a file's task panicked or was cancelled
+    #[error("file processing task failed: {0}")]
+    Task(#[from] JoinError),
+
+    // problems with how we were asked to run: configuration, arguments, file locations
+    #[error("{0}")]
+    Setup(String),
+}
+
+impl Error {
+    // a coarse classification, stored with dead-lettered files and used to filter retries
+    pub fn category(&self) -> &'static str {
+        match self {
+            Error::MissingDimension(_) => "missing_dimension",
+            Error::MissingVariable(_) => "missing_variable",
+            Error::MissingAttribute { .. } => "missing_attribute",
+            Error::BadAttributeType { .. } => "bad_attribute_type",
+            Error::StringDecode { .. } => "string_decode",
+            Error::Merge(_) => "merge",
+            Error::PresConflict { .. } => "pres_conflict",
+            Error::NetCdf(_) => "netcdf",
+            Error::Mongo(_) => "mongo",
+            Error::Serialize(_) | Error::Json(_) => "serialize",
+            Error::Io(_) => "io",
+            Error::Task(e) if e.is_panic() => "panic",
+            Error::Task(_) => "cancelled",
+            Error::Setup(_) => "setup",
+        }
+    }
+}
@@ -0,0 +1,82 @@
+// dead-letter record of files that failed to ingest, so they can be retried once the parser is fixed
+use std::collections::HashSet;
+use std::fs;
+use futures::stream::TryStreamExt;
+use mongodb::Collection;
+use mongodb::bson::{doc, Bson, Document};
+use mongodb::options::{FindOptions, UpdateOptions};
+use crate::error::Error;
+use crate::retry::RetryPolicy;
+
+pub struct FailureLog {
+    collection: Collection<Document>,
+    // files with an entry in the collection, so successes only touch it when there is something to clear
+    known: HashSet<String>,
+    retry: RetryPolicy,
+}
+
+impl FailureLog {
+    pub async fn open(collection: Collection<Document>, retry: RetryPolicy) -> Result<FailureLog, mongodb::error::Error> {
+        let mut known = HashSet::new();
+        let mut cursor = collection.find(None, None).await?;
+        while let Some(entry) = cursor.try_next().await? {
+            if let Ok(filename) = entry.get_str("_id") {
+                known.insert(filename.to_string());
+            }
+        }
+        Ok(FailureLog { collection, known, retry })
+    }
+
+    // the recorded files, optionally only those that failed with one of categories
+    pub async fn filenames(&self, categories: &[String]) -> Result<Vec<String>, mongodb::error::Error> {
+        let filter = if categories.is_empty() {
+            None
+        } else {
+            Some(doc! { "category": { "$in": categories } })
+        };
+        let options = FindOptions::builder().sort(doc! { "_id": 1 }).build();
+        let mut filenames = Vec::new();
+        let mut cursor = self.collection.find(filter, options).await?;
+        while let Some(entry) = cursor.try_next().await? {
+            if let Ok(filename) = entry.get_str("_id") {
+                filenames.push(filename.to_string());
+            }
+        }
+        Ok(filenames)
+    }
+
+    pub async fn record(&mut self, filename: &str, category: &str, message: &str) -> Result<(), Error> {
+        let metadata = fs::metadata(filename).ok();
+        let size = metadata.as_ref().map_or(Bson::Null, |m| Bson::Int64(m.len() as i64));
+        let mtime = metadata
+            .and_then(|m| m.modified().ok())
+            .map_or(Bson::Null, |t| Bson::String(chrono::DateTime::<chrono::Utc>::from(t).to_rfc3339()));
+        let update = doc! {
+            "$set": {
+                "category": category,
+                "message": message,
+                "size": size,
+                "mtime": mtime,
+                "failed": mongodb::bson::DateTime::now(),
+            },
+            "$inc": { "attempts": 1 },
+        };
+        let options = UpdateOptions::builder().upsert(true).build();
+        let what = format!("Recording the failure of {}", filename);
+        let collection = &self.collection;
+        self.retry.run(&what, || async { Ok(collection.update_one(doc! { "_id": filename }, update.clone(), options.clone()).await?) }).await?;
+        self.known.insert(filename.to_string());
+        Ok(())
+    }
+
+    // filename went through, so it no longer needs retrying
+    pub async fn clear(&mut self, filename: &str) -> Result<(), Error> {
+        if self.known.contains(filename) {
+            let what = format!("Clearing the failure of {}", filename);
+            let collection = &self.collection;
+            self.retry.run(&what, || async { Ok(collection.delete_one(doc! { "_id": filename }, None).await?) }).await?;
+            self.known.remove(filename);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,89 @@
+// lists of files to process, as plain paths or itemized rsync output
+use std::io::{self, BufRead};
+use std::path::Path;
+
+// a file to process, and whether the list it came from says it was deleted
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FileEntry {
+    pub path: String,
+    pub deleted: bool,
+}
+
+impl FileEntry {
+    // a file named without saying what happened to it, as in a plain list or a walk
+    pub fn listed(path: impl Into<String>) -> Self {
+        FileEntry { path: path.into(), deleted: false }
+    }
+}
+
+// turn one line of an rsync file list into the local file it refers to. Plain paths are
+// used as-is; lines of itemized output (rsync -i), including *deleting lines, carry paths
+// relative to the rsync destination, so are resolved against rsync_root, and only their
+// netCDF files are kept.
+pub fn rsync_entry_path(line: &str, rsync_root: &Path) -> Option<FileEntry> {
+    let (relative, deleted) = if let Some(path) = line.strip_prefix("*deleting") {
+        (path.trim(), true)
+    } else if is_itemized(line) {
+        (line[12..].trim(), false)
+    } else {
+        return Some(FileEntry::listed(line));
+    };
+    if !relative.ends_with(".nc") {
+        return None;
+    }
+    Some(FileEntry { path: rsync_root.join(relative).to_string_lossy().into_owned(), deleted })
+}
+
+// itemized changes look like ">f.st...... aoml/1901727/profiles/R1901727_357.nc"
+fn is_itemized(line: &str) -> bool {
+    let bytes = line.as_bytes();
+    bytes.len() > 12
+        && b"<>ch.*".contains(&bytes[0])
+        && b"fdLDS".contains(&bytes[1])
+        && bytes[11] == b' '
+}
+
+pub fn read_file_list<R: BufRead>(reader: R) -> io::Result<Vec<String>> {
+    let mut files = Vec::new();
+    for line in reader.lines() {
+        let line = line?;
+        let line = line.trim();
+        if !line.is_empty() {
+            files.push(line.to_string());
+        }
+    }
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plain_paths_are_used_as_is() {
+        let root = Path::new("/bulk/ifremer");
+        assert_eq!(rsync_entry_path("/bulk/ifremer/aoml/1901727/profiles/R1901727_357.nc", root), Some(FileEntry::listed("/bulk/ifremer/aoml/1901727/profiles/R1901727_357.nc")));
+    }
+
+    #[test]
+    fn itemized_paths_start_after_the_change_flags() {
+        let root = Path::new("/bulk/ifremer");
+        assert_eq!(rsync_entry_path(">f.st...... aoml/1901727/profiles/R1901727_357.nc", root), Some(FileEntry::listed("/bulk/ifremer/aoml/1901727/profiles/R1901727_357.nc")));
+        assert_eq!(rsync_entry_path(">f+++++++++ aoml/1901727/profiles/R1901727_357D.nc", root), Some(FileEntry::listed("/bulk/ifremer/aoml/1901727/profiles/R1901727_357D.nc")));
+    }
+
+    #[test]
+    fn deleting_lines_carry_the_deletion() {
+        let root = Path::new("/bulk/ifremer");
+        let entry = rsync_entry_path("*deleting   aoml/1901727/profiles/R1901727_357.nc", root).unwrap();
+        assert_eq!(entry, FileEntry { path: "/bulk/ifremer/aoml/1901727/profiles/R1901727_357.nc".to_string(), deleted: true });
+        assert_eq!(rsync_entry_path("*deleting   aoml/1901727/profiles/", root), None);
+    }
+
+    #[test]
+    fn itemized_entries_other_than_netcdf_files_are_dropped() {
+        let root = Path::new("/bulk/ifremer");
+        assert_eq!(rsync_entry_path("cd+++++++++ aoml/1901727/profiles/", root), None);
+        assert_eq!(rsync_entry_path(">f.st...... aoml/1901727/1901727_meta.txt", root), None);
+    }
+}
@@ -0,0 +1,210 @@
+// run files through the parser and into a sink, recording what became of each
+use tokio;
+use crate::config::{self, Config};
+use crate::error::Error;
+use crate::failures::FailureLog;
+use crate::file_list::FileEntry;
+use crate::merge;
+use crate::parse::{parse_file, SourceFileVersion};
+use crate::runs::RunSummary;
+use crate::schema::ProfileDoc;
+use crate::sink::{Sink, WriteOutcome};
+use futures::stream::{self, StreamExt};
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+// what to make of each file
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Documents {
+    // a document for each of its profiles
+    Profiles,
+    // it is a B-file: a document for each of its profiles merged with the same profile
+    // of the core file alongside it
+    Merged,
+}
+
+// process every file, up to `concurrency` at a time, and keep going past bad files.
+// Each file is parsed and handed to the sink in its own task.
+// Failed files are recorded in, and recovered files cleared from, failure_log if there is one.
+pub async fn ingest(
+    files: Vec<FileEntry>,
+    concurrency: usize,
+    documents: Documents,
+    sink: Arc<dyn Sink>,
+    mut failure_log: Option<FailureLog>,
+    config: &Config,
+    mut summary: RunSummary,
+) -> Result<RunSummary, Error> {
+    summary.files = files.len();
+    let source = Arc::new(config.source.clone());
+    let mut results = stream::iter(files)
+        .map(|entry| {
+            let filename = entry.path.clone();
+            let task = tokio::spawn(process_file(entry, documents, sink.clone(), source.clone()));
+            async move { (filename, task.await.map_err(Error::from)) }
+        })
+        .buffer_unordered(concurrency);
+    while let Some((filename, outcome)) = results.next().await {
+        let failure = match outcome {
+            Ok(Ok(file_outcome)) => {
+                match file_outcome {
+                    FileOutcome::Written => {}
+                    FileOutcome::Unchanged => summary.unchanged += 1,
+                    FileOutcome::Stale => summary.stale += 1,
+                    FileOutcome::Deleted => summary.deleted += 1,
+                }
+                None
+            }
+            // a panic while parsing is recorded like any other failure rather than ending the run
+            Ok(Err(e)) | Err(e) => {
+                eprintln!("Failed to process file {}: {}", filename, e);
+                Some((e.category(), e.to_string()))
+            }
+        };
+        if let Some(log) = failure_log.as_mut() {
+            let logged = match &failure {
+                Some((category, message)) => log.record(&filename, category, message).await,
+                None => log.clear(&filename).await,
+            };
+            // the file's own outcome stands, and the run goes on to finish
+            if let Err(e) = logged {
+                eprintln!("Failed to update the failure log for {}: {}", filename, e);
+            }
+        }
+        if let Some((_, message)) = failure {
+            summary.fail(&filename, message);
+        }
+    }
+    let report = sink.finish().await?;
+    for duplicate in &report.duplicates {
+        eprintln!("Duplicate key in {}: {}", duplicate.collection, duplicate.id);
+    }
+    for rejected in &report.rejected {
+        let reason = format!("failed to insert {} into {}: {}", rejected.id, rejected.collection, rejected.message);
+        eprintln!("{}", reason);
+        summary.fail(&rejected.source_file, reason);
+    }
+    eprintln!("Wrote {} profiles; {} duplicate keys, {} other write errors", report.profiles_written, report.duplicates.len(), report.rejected.len());
+    summary.profiles_written = report.profiles_written;
+    summary.finish();
+    eprintln!(
+        "Processed {} files in {:.0}s: {} unchanged, {} older than what is stored, {} deleted upstream, {} failed",
+        summary.files, summary.duration_seconds, summary.unchanged, summary.stale, summary.deleted, summary.failure_count,
+    );
+
+    Ok(summary)
+}
+
+// what became of one file
+pub enum FileOutcome {
+    // parsed and handed to the sink
+    Written,
+    // same contents as the version already stored, so left alone
+    Unchanged,
+    // parsed, but what is stored has the same or a later DATE_UPDATE
+    Stale,
+    // gone from the mirror, so its documents were removed
+    Deleted,
+}
+
+// ingest one netCDF file, replacing whatever it previously contributed
+async fn process_file(entry: FileEntry, documents: Documents, sink: Arc<dyn Sink>, source: Arc<config::Source>) -> Result<FileOutcome, Error> {
+
+    let FileEntry { path: filename, deleted } = entry;
+    let source_file = source.url(&filename)?;
+
+    // a file rsync removed upstream stays removed
+    if is_deleted(&filename, deleted)? {
+        sink.delete_file(&source_file).await?;
+        eprintln!("Deleted contents of file: {}", source_file);
+        return Ok(FileOutcome::Deleted);
+    }
+
+    let core_filename = match documents {
+        Documents::Profiles => None,
+        Documents::Merged => Some(merge::core_file_for(&filename).ok_or_else(|| Error::Merge(format!("no core file alongside {}", filename)))?),
+    };
+
+    // rsync touches plenty of files without changing them
+    let version = {
+        let filename = filename.clone();
+        let core_filename = core_filename.clone();
+        tokio::task::spawn_blocking(move || -> io::Result<SourceFileVersion> {
+            let version = SourceFileVersion::read(&filename)?;
+            match core_filename {
+                Some(core_filename) => Ok(merge::merged_version(&version, &SourceFileVersion::read(&core_filename)?)),
+                None => Ok(version),
+            }
+        }).await??
+    };
+    if sink.is_unchanged(&source_file, &version.sha256).await? {
+        eprintln!("Unchanged file: {}", filename);
+        return Ok(FileOutcome::Unchanged);
+    }
+
+    let profiles = match core_filename {
+        Some(core_filename) => {
+            let core_source_file = source.url(&core_filename)?;
+            load_merged_file(filename, source_file.clone(), core_filename, core_source_file, version).await?
+        }
+        None => load_file(filename, source_file.clone(), version).await?,
+    };
+    match sink.write_file(&source_file, profiles).await? {
+        WriteOutcome::Written => Ok(FileOutcome::Written),
+        WriteOutcome::Stale => {
+            eprintln!("Kept newer stored version of file: {}", source_file);
+            Ok(FileOutcome::Stale)
+        }
+    }
+}
+
+// a missing file was deleted upstream if rsync said so, or if its profiles directory is
+// still there; otherwise the mirror is not mounted or the path is wrong, and deleting its
+// documents would be a mistake
+fn is_deleted(filename: &str, listed_as_deleted: bool) -> Result<bool, Error> {
+    let path = Path::new(filename);
+    if path.exists() {
+        return Ok(false);
+    }
+    match path.parent() {
+        _ if listed_as_deleted => Ok(true),
+        Some(dir) if dir.is_dir() => Ok(true),
+        _ => Err(Error::Setup(format!("{} does not exist, and neither does its directory; is the mirror mounted?", filename))),
+    }
+}
+
+// netCDF reads block, so decode the file on the blocking thread pool
+async fn load_file(filename: String, source_file: String, version: SourceFileVersion) -> Result<Vec<ProfileDoc>, Error> {
+    eprintln!("Processing file: {}", filename);
+    tokio::task::spawn_blocking(move || parse_file(&filename, &source_file, &version)).await?
+}
+
+async fn load_merged_file(filename: String, source_file: String, core_filename: String, core_source_file: String, version: SourceFileVersion) -> Result<Vec<ProfileDoc>, Error> {
+    eprintln!("Merging file: {} with {}", filename, core_filename);
+    tokio::task::spawn_blocking(move || merge::parse_merged_file(&filename, &source_file, &core_filename, &core_source_file, &version)).await?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn missing_files_are_only_deleted_when_their_directory_is_there() {
+        let dir = TempDir::new().unwrap();
+        let dir = dir.path();
+        let present = dir.join("R1901727_357.nc");
+        fs::write(&present, b"").unwrap();
+        let present = present.to_string_lossy();
+        let gone = dir.join("R1901727_358.nc").to_string_lossy().into_owned();
+        let unmounted = dir.join("unmounted/R1901727_358.nc").to_string_lossy().into_owned();
+
+        assert!(!is_deleted(&present, false).unwrap());
+        assert!(!is_deleted(&present, true).unwrap());
+        assert!(is_deleted(&gone, false).unwrap());
+        assert!(is_deleted(&unmounted, true).unwrap());
+        assert!(matches!(is_deleted(&unmounted, false), Err(Error::Setup(_))));
+    }
+}
@@ -0,0 +1,87 @@
+// record of source files a rebuild has fully committed, so a restarted rebuild can skip them
+use std::collections::HashSet;
+use crate::error::Error;
+use std::fs::{File, OpenOptions};
+use std::io::{BufReader, Write};
+use std::path::Path;
+use std::sync::Arc;
+use futures::stream::TryStreamExt;
+use mongodb::Collection;
+use mongodb::bson::{doc, Document};
+use mongodb::error::ErrorKind;
+use mongodb::options::InsertManyOptions;
+use crate::bulk::DUPLICATE_KEY;
+use crate::retry::RetryPolicy;
+use tokio::sync::Mutex;
+
+pub enum Journal {
+    // one source_file per line, appended as files are committed
+    File(File),
+    // one document per source_file, keyed by _id
+    Collection(Collection<Document>, RetryPolicy),
+}
+
+// a rebuild writing several pairs of collections keeps one journal for all of them
+pub type SharedJournal = Arc<Mutex<Journal>>;
+
+impl Journal {
+    // open or create a local journal file, returning it with the source files it already lists
+    pub fn open_file(path: &Path) -> Result<(Journal, HashSet<String>), Error> {
+        let completed: HashSet<String> = if path.exists() {
+            crate::file_list::read_file_list(BufReader::new(File::open(path)?))?.into_iter().collect()
+        } else {
+            HashSet::new()
+        };
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok((Journal::File(file), completed))
+    }
+
+    // use a Mongo collection as the journal, returning it with the source files it already lists
+    pub async fn open_collection(collection: Collection<Document>, retry: RetryPolicy) -> Result<(Journal, HashSet<String>), Error> {
+        let mut completed = HashSet::new();
+        let mut cursor = collection.find(None, None).await?;
+        while let Some(entry) = cursor.try_next().await? {
+            if let Ok(source_file) = entry.get_str("_id") {
+                completed.insert(source_file.to_string());
+            }
+        }
+        Ok((Journal::Collection(collection, retry), completed))
+    }
+
+    pub async fn record(&mut self, source_files: &[String]) -> Result<(), Error> {
+        if source_files.is_empty() {
+            return Ok(());
+        }
+        match self {
+            Journal::File(file) => {
+                for source_file in source_files {
+                    writeln!(file, "{}", source_file)?;
+                }
+                file.flush()?;
+            }
+            Journal::Collection(collection, retry) => {
+                let now = mongodb::bson::DateTime::now();
+                let entries: Vec<Document> = source_files.iter().map(|source_file| doc! { "_id": source_file, "completed": now }).collect();
+                let options = InsertManyOptions::builder().ordered(false).build();
+                let what = format!("Journaling {} files", entries.len());
+                let result = retry.run(&what, || async { Ok(collection.insert_many(entries.iter(), options.clone()).await?) }).await;
+                if let Err(e) = result {
+                    // a file that is already journaled, perhaps by an attempt that was retried, is fine;
+                    // anything else is not
+                    let only_duplicates = match &e {
+                        Error::Mongo(err) => match &*err.kind {
+                            ErrorKind::BulkWrite(failure) => failure.write_concern_error.is_none()
+                                && failure.write_errors.iter().flatten().all(|err| err.code == DUPLICATE_KEY),
+                            _ => false,
+                        },
+                        _ => false,
+                    };
+                    if !only_duplicates {
+                        return Err(e);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
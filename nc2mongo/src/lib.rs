@@ -0,0 +1,24 @@
+// Argo profile netCDF decoding and the machinery for loading it into MongoDB;
+// parse_profile_file and the schema types are all another tool needs to read profiles
+#![allow(nonstandard_style)]
+
+pub mod bulk;
+pub mod config;
+pub mod dns;
+pub mod error;
+pub mod failures;
+pub mod file_list;
+pub mod ingest;
+pub mod journal;
+pub mod merge;
+pub mod parse;
+pub mod retry;
+pub mod runs;
+pub mod schema;
+pub mod sink;
+pub mod update;
+pub mod walk;
+
+pub use error::Error;
+pub use parse::parse_profile_file;
+pub use schema::{DataInfo, DataSchema, GeoJSONPoint, LevelValues, MapSchema, ProfileDoc, SamplingScheme};
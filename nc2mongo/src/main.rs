@@ -1,518 +1,439 @@
 #![allow(nonstandard_style)]
-use netcdf;
-use tokio;
-use std::error::Error;
-use std::env;
-use mongodb::bson::{doc};
-use mongodb::{Client, options::{ClientOptions, ResolverConfig}};
-use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-
-// helper functions ///////////////////////////////////////////
-
-fn trim_null_bytes(input: String) -> String {
-    input.trim().trim_end_matches('\0').to_string()
+use mongodb::{Client, Database};
+use mongodb::error::{ErrorKind, WriteFailure};
+use std::collections::{BTreeMap, HashSet};
+use std::fs::File;
+use std::io::{self, BufReader};
+use clap::{Parser, Subcommand};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use nc2mongo::bulk::DUPLICATE_KEY;
+use nc2mongo::config::Config;
+use nc2mongo::error::Error;
+use nc2mongo::failures::FailureLog;
+use nc2mongo::file_list::{read_file_list, rsync_entry_path, FileEntry};
+use nc2mongo::ingest::{ingest, Documents};
+use nc2mongo::journal::{Journal, SharedJournal};
+use nc2mongo::merge;
+use nc2mongo::retry::RetryPolicy;
+use nc2mongo::runs::RunSummary;
+use nc2mongo::sink::{check_transactions, MongoSink, NdjsonSink, Sink, SplitSink};
+use nc2mongo::update;
+use nc2mongo::walk::{self, FilePrefix, WalkFilter};
+
+// command line interface /////////////////////////////////////
+
+/// Load Argo profile netCDF files into MongoDB
+#[derive(Parser, Debug)]
+#[command(args_conflicts_with_subcommands = true)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// TOML file of database, collection and source URL settings; defaults to $NC2MONGO_CONFIG if that is set
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+
+    #[command(flatten)]
+    ingest: IngestArgs,
+
+    #[command(flatten)]
+    common: CommonArgs,
 }
 
-fn unpack_string(name: &str, buflen: usize, extents: netcdf::Extents, file: &netcdf::File) -> String {
-    let mut dump = vec![0_u8; buflen];
-    if let Some(variable) = file.variable(name) {
-        if let Ok(_) = variable.get_raw_values(&mut dump, extents) {
-            if let Ok(string) = String::from_utf8(dump) {
-                return trim_null_bytes(string);
-            }
-        }
-    }
-    String::new()
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// process the rsyncupdates list in the newest rsync log directory
+    Update(UpdateArgs),
+    /// reprocess the files recorded in the ingest_failures collection, e.g. after a parser fix
+    RetryFailures(RetryArgs),
+}
+
+#[derive(clap::Args, Debug)]
+struct IngestArgs {
+    /// netCDF files to process
+    files: Vec<String>,
+
+    /// read more netCDF file paths from this file, one per line; use - to read from stdin.
+    /// Itemized rsync output (rsync -i), including *deleting lines, is understood too
+    #[arg(long)]
+    files_from: Option<String>,
+
+    /// walk the GDAC mirror rooted here, laid out as <dac>/<wmo>/profiles/, and process the profile files found
+    #[arg(long)]
+    walk: Option<PathBuf>,
+
+    /// with --walk, only visit this DAC; repeat for several
+    #[arg(long = "dac", requires = "walk")]
+    dacs: Vec<String>,
+
+    /// with --walk, skip floats with a smaller WMO number
+    #[arg(long, requires = "walk")]
+    wmo_min: Option<u64>,
+
+    /// with --walk, skip floats with a larger WMO number
+    #[arg(long, requires = "walk")]
+    wmo_max: Option<u64>,
+
+    /// with --walk, only process files with this prefix; repeat for several. Defaults to all of R, D, BR, BD, SR and SD
+    #[arg(long = "prefix", value_enum, requires = "walk")]
+    prefixes: Vec<FilePrefix>,
+
+    /// full rebuild into empty collections: skip the per-file delete and upserts, and insert documents in unordered batches
+    #[arg(long, conflicts_with = "dry_run")]
+    rebuild: bool,
+
+    /// with --rebuild, the number of profiles to buffer per insert_many
+    #[arg(long, default_value_t = 1000, requires = "rebuild")]
+    batch_size: usize,
+
+    /// with --rebuild, record committed source files in this local file and skip files it already lists
+    #[arg(long, requires = "rebuild", conflicts_with = "journal_collection")]
+    journal: Option<PathBuf>,
+
+    /// with --rebuild, like --journal but kept in this collection of the argo database
+    #[arg(long, requires = "rebuild")]
+    journal_collection: Option<String>,
+
+    /// build the merged view instead: join each B-file's profiles with those of the core file
+    /// alongside it, into argo_merged and argo_merged_search. A core file stands for the B-file
+    /// alongside it; other files are skipped
+    #[arg(long)]
+    merged: bool,
+}
+
+#[derive(clap::Args, Debug)]
+struct UpdateArgs {
+    /// directory holding one subdirectory of logs per rsync run
+    #[arg(long, default_value = "/logs/ifremer")]
+    log_root: PathBuf,
+
+    /// refuse to process a log directory last modified more than this many hours ago
+    #[arg(long, default_value_t = 36)]
+    max_age_hours: u64,
+
+    /// process the newest log directory even if it was already processed or is too old
+    #[arg(long)]
+    force: bool,
+
+    #[command(flatten)]
+    common: CommonArgs,
+}
+
+#[derive(clap::Args, Debug)]
+struct RetryArgs {
+    /// only retry files that failed with this error category; may be repeated
+    #[arg(long = "category")]
+    categories: Vec<String>,
+
+    #[command(flatten)]
+    common: CommonArgs,
+}
+
+#[derive(clap::Args, Debug)]
+struct CommonArgs {
+    /// maximum number of files to process at once; defaults to the number of available CPUs
+    #[arg(long)]
+    concurrency: Option<usize>,
+
+    /// directory that relative paths in itemized rsync output are resolved against
+    #[arg(long, default_value = "/bulk/ifremer")]
+    rsync_root: PathBuf,
+
+    /// where documents go: upserted into MongoDB, or printed as newline-delimited JSON
+    #[arg(long, value_enum)]
+    output: Option<OutputFormat>,
+
+    /// with --output ndjson, write to this file instead of stdout
+    #[arg(long)]
+    output_file: Option<PathBuf>,
+
+    /// leave the database alone and print the documents that would be written; same as --output ndjson
+    #[arg(long, conflicts_with = "output")]
+    dry_run: bool,
+
+    /// rewrite every file even if its contents are unchanged or what is stored has the same or a later DATE_UPDATE,
+    /// so that parser fixes reach documents already in the database
+    #[arg(long)]
+    reprocess: bool,
+
+    /// how many times to try a MongoDB write that fails for a transient reason, such as a network blip or an election
+    #[arg(long, default_value_t = 5)]
+    retry_attempts: u32,
+
+    /// wait this many milliseconds before the first retry, doubling for each retry after it
+    #[arg(long, default_value_t = 500)]
+    retry_delay_ms: u64,
+
+    /// never wait more than this many seconds between retries
+    #[arg(long, default_value_t = 30)]
+    retry_max_delay_secs: u64,
 }
 
-fn unpack_string_array(name: &str, buflen: usize, arraydim: usize, extents: netcdf::Extents, file: &netcdf::File) -> Vec<String> {
-    let mut dump = vec![0_u8; buflen * arraydim];
-    if let Some(variable) = file.variable(name) {
-        if let Ok(_) = variable.get_raw_values(&mut dump, extents) {
-            let strings: Vec<String> = dump
-                .chunks_exact(buflen)
-                .map(|chunk| {
-                    let string: String = String::from_utf8_lossy(chunk).into_owned().parse().unwrap_or_default();
-                    string.trim().to_string(); // Strip leading and trailing whitespace
-                    trim_null_bytes(string)
-                })
-                .collect();
-            return strings;
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+enum OutputFormat {
+    Mongo,
+    Ndjson,
+}
+
+impl CommonArgs {
+    fn concurrency(&self) -> usize {
+        self.concurrency.unwrap_or_else(|| {
+            std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1)
+        }).max(1)
+    }
+
+    fn retry_policy(&self) -> RetryPolicy {
+        RetryPolicy {
+            attempts: self.retry_attempts.max(1),
+            initial_delay: Duration::from_millis(self.retry_delay_ms),
+            max_delay: Duration::from_secs(self.retry_max_delay_secs),
         }
     }
-    vec![String::new(); arraydim]
-}
 
-fn split_string(input: String, separator: char) -> Vec<String> {
-    input.split(separator).map(|s| s.trim().to_string()).collect()
+    // the NDJSON sink to use instead of the database, if one was asked for
+    fn ndjson_sink(&self, config: &Config, documents: Documents) -> io::Result<Option<Arc<dyn Sink>>> {
+        if self.dry_run || self.output == Some(OutputFormat::Ndjson) {
+            let collections = &config.collections;
+            let core = NdjsonSink::create(self.output_file.as_deref(), collections)?;
+            let sink: Arc<dyn Sink> = match documents {
+                Documents::Profiles => {
+                    let synthetic = core.with_collections(&collections.argo_synthetic, &collections.argo_synthetic_search);
+                    Arc::new(SplitSink::new(core, synthetic))
+                }
+                Documents::Merged => Arc::new(core.with_collections(&collections.argo_merged, &collections.argo_merged_search)),
+            };
+            Ok(Some(sink))
+        } else {
+            Ok(None)
+        }
+    }
 }
 
 ////////////////////////////////////////////////////////////////
 
 #[tokio::main]
-async fn main() -> Result<(), Box<dyn Error>> {
+async fn main() -> Result<(), Error> {
     
-    // Read the command line argument as file name of interest
-    let filename = std::env::args().nth(1).expect("Missing data directory argument");
+    let cli = Cli::parse();
+    let config = Config::load(cli.config.as_deref())?;
+    let failures = match cli.command {
+        Some(Command::Update(args)) => run_update(args, &config).await?,
+        Some(Command::RetryFailures(args)) => run_retry_failures(args, &config).await?,
+        None => run_ingest(cli.ingest, cli.common, &config).await?,
+    };
+    if failures > 0 {
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
 
-    // mongodb setup ///////////////////////////////////////////
-    // Load the MongoDB connection string from an environment variable:
-    let client_uri =
-       env::var("MONGODB_URI").expect("You must set the MONGODB_URI environment var!"); 
+// mongodb setup ///////////////////////////////////////////
+async fn connect(config: &Config) -> Result<Client, Error> {
+    // The MongoDB connection string comes from the config file or the MONGODB_URI environment variable:
+    let client_uri = config.mongodb_uri.as_deref()
+       .ok_or_else(|| Error::Setup("You must set the MONGODB_URI environment var!".to_string()))?;
 
     // A Client is needed to connect to MongoDB:
-    // An extra line of code to work around a DNS issue on Windows:
-    let options =
-       ClientOptions::parse_with_resolver_config(&client_uri, ResolverConfig::cloudflare())
-          .await?;
-    let client = Client::with_options(options)?; 
-    let argo = client.database("argo").collection::<DataSchema>("argo");
-    let argo_search = client.database("argo").collection::<DataSchema>("argo_search");
-
-    // structs to describe documents //////////////////////////////
-
-    #[derive(Serialize, Deserialize, Debug, Clone)]
-    struct GeoJSONPoint {
-        #[serde(rename = "type")]
-        location_type: String,
-        coordinates: [f64; 2],
-    } 
-
-    #[derive(Serialize, Deserialize, Debug, Clone)]
-    struct DataInfo {
-        DATA_MODE: String,
-        UNITS: String,
-        LONG_NAME: String,
-        PROFILE_PARAMETER_QC: String,
-    } 
-
-    // todo: add timestamp added as a simple versioning mechanism
-    #[derive(Serialize, Deserialize, Debug, Clone)]
-    struct DataSchema {
-        _id: String,
-        geolocation: GeoJSONPoint,
-        CYCLE_NUMBER: i32,
-        DIRECTION: String,
-        DATA_STATE_INDICATOR: String,
-        DATA_MODE: String,
-        DATE_CREATION: String,
-        DATE_UPDATE: String,
-        DC_REFERENCE: String,
-        JULD: f64,
-        JULD_QC: String,
-        JULD_LOCATION: f64,
-        POSITION_QC: String,
-        VERTICAL_SAMPLING_SCHEME: String,
-        CONFIG_MISSION_NUMBER: i32,
-        STATION_PARAMETERS: Vec<String>,
-        realtime_data: Option<HashMap<String, Vec<f64>>>,
-        adjusted_data: Option<HashMap<String, Vec<f64>>>,
-        data_info: Option<HashMap<String, DataInfo>>,
-        level_qc: Option<HashMap<String, Vec<String>>>,
-        adjusted_level_qc: Option<HashMap<String, Vec<String>>>,
-        DATA_TYPE: String,
-        FORMAT_VERSION: String,
-        HANDBOOK_VERSION: String,
-        REFERENCE_DATE_TIME: String,
-        PROJECT_NAME: String,   
-        PI_NAME: Vec<String>,
-        DATA_CENTRE: String,
-        PLATFORM_TYPE: String,
-        PLATFORM_NUMBER: String,
-        FLOAT_SERIAL_NO: String,
-        FIRMWARE_VERSION: String,
-        WMO_INST_TYPE: String,
-        POSITIONING_SYSTEM: String,
-        source_file: String,
-    }
+    // mongodb+srv lookups go through the configured DNS resolver
+    let options = config.dns.client_options(client_uri).await?;
+    Ok(Client::with_options(options)?)
+}
 
-    #[derive(Serialize, Deserialize, Debug, Clone)]
-    struct MapSchema {
-        _id: String,
-        geolocation: GeoJSONPoint,
-        JULD: f64,
-        STATION_PARAMETERS: Vec<String>,
-        source_file: String,
-    }
-    // construct link to upstream netcdf file
-    let parts: Vec<&str> = filename.split("ifremer/").collect();
-    let source_file = format!("ftp://ftp.ifremer.fr/ifremer/argo/dac/{}", parts.get(1).unwrap());
-
-    // remove previous content from this file
-    // todo: surely there is a better way to do this; at least skip this via env variable when doing full rebuild
-    argo.delete_many(doc! { "source_file": source_file.clone() }, None).await?;
-    argo_search.delete_many(doc! { "source_file": source_file.clone() }, None).await?;
-
-    // open the file or inform the user the profile has been dropped
-    println!("Processing file: {}", filename.clone());
-    let id = filename
-        .rsplit('/')
-        .next()
-        .and_then(|name| name.strip_suffix(".nc"))
-        .unwrap_or("");
-    let file = match netcdf::open(&filename.clone()) {
-        Ok(file) => file,
-        Err(_e) => {
-            eprintln!("Deleted contents of file: {}", source_file);
-            std::process::exit(1);
-        }
+// replace each file's documents in argo and argo_search, or for S-files in their synthetic
+// counterparts; merged documents go to argo_merged and argo_merged_search
+async fn upsert_sink(db: &Database, config: &Config, documents: Documents, common: &CommonArgs) -> Result<Arc<dyn Sink>, Error> {
+    check_transactions(db).await?;
+    let collections = &config.collections;
+    let upsert = |argo: &str, argo_search: &str| {
+        MongoSink::upsert(db.collection(argo), db.collection(argo_search), db.collection(&collections.argo_history), common.retry_policy())
+            .reprocessing(common.reprocess)
     };
-
-    // loop over internal profiles
-    let N_PROF: usize = file.dimension("N_PROF").unwrap().len();
-    for pfl in 0..N_PROF {
-
-        // data unpacking /////////////////////////////////////////////
-        let pindex = 0; // just use the first profile for now
-        let STRING1: usize = 1;
-        let STRING2: usize = 2;
-        let STRING4: usize = 4;
-        let STRING8: usize = 8;
-        let STRING16: usize = 16;
-        let STRING32: usize = 32;
-        let STRING64: usize = 64;
-        let STRING256: usize = 256;
-        let DATE_TIME: usize = 14;
-        
-        let N_PARAM: usize = file.dimension("N_PARAM").unwrap().len();
-        let N_LEVELS: usize = file.dimension("N_LEVELS").unwrap().len();
-        //let N_CALIB: usize = file.dimension("N_CALIB").unwrap().len();
-        //let N_HISTORY: usize = file.dimension("N_HISTORY").unwrap().len();
-    
-        let DATA_TYPE: String = unpack_string("DATA_TYPE", STRING16, [..16].into(), &file);
-        let FORMAT_VERSION: String = unpack_string("FORMAT_VERSION", STRING4, [..4].into(), &file);
-        let HANDBOOK_VERSION: String = unpack_string("HANDBOOK_VERSION", STRING4, [..4].into(), &file);
-        let REFERENCE_DATE_TIME: String = unpack_string("REFERENCE_DATE_TIME", DATE_TIME, [..14].into(), &file);
-        let DATE_CREATION: String = unpack_string("DATE_CREATION", DATE_TIME, [..14].into(), &file);
-        let DATE_UPDATE: String = unpack_string("DATE_UPDATE", DATE_TIME, [..14].into(), &file);
-        let PLATFORM_NUMBER: String = unpack_string("PLATFORM_NUMBER", STRING8, [pfl..(pfl+1), 0..8].into(), &file);
-        let PROJECT_NAME: String = unpack_string("PROJECT_NAME", STRING64, [pfl..(pfl+1), 0..64].into(), &file);
-        let PI_NAME: String = unpack_string("PI_NAME", STRING64, [pfl..(pfl+1), 0..64].into(), &file);
-        let namesize: usize = file.variable("STATION_PARAMETERS").unwrap().dimensions()[2].len();
-        let STATION_PARAMETERS: Vec<String> = unpack_string_array(
-            "STATION_PARAMETERS",
-            match namesize {
-                1 => STRING1,
-                2 => STRING2,
-                4 => STRING4,
-                8 => STRING8,
-                16 => STRING16,
-                32 => STRING32,
-                64 => STRING64,
-                256 => STRING256,
-                _ => panic!("Unsupported namesize: {}", namesize),
-            },
-            N_PARAM,
-            [pfl..(pfl+1), 0..N_PARAM, 0..namesize].into(),
-            &file,
-        );
-        let CYCLE_NUMBER: i32 = file.variable("CYCLE_NUMBER").map(|var| var.get_value([pindex]).unwrap_or(99999)).unwrap_or(99999);
-        let DIRECTION: String = unpack_string("DIRECTION", STRING1, [pfl..(pfl+1)].into(), &file);
-        let DATA_CENTRE: String = unpack_string("DATA_CENTRE", STRING2, [pfl..(pfl+1), 0..2].into(), &file);
-        let DC_REFERENCE: String = unpack_string("DC_REFERENCE", STRING32, [pfl..(pfl+1), 0..32].into(), &file);
-        let DATA_STATE_INDICATOR: String = unpack_string("DATA_STATE_INDICATOR", STRING4, [pfl..(pfl+1), 0..4].into(), &file);
-        let DATA_MODE: String = unpack_string("DATA_MODE", STRING1, [pfl..(pfl+1)].into(), &file);
-        let PLATFORM_TYPE: String = unpack_string("PLATFORM_TYPE", STRING32, [pfl..(pfl+1), 0..32].into(), &file);
-        let FLOAT_SERIAL_NO: String = unpack_string("FLOAT_SERIAL_NO", STRING32, [pfl..(pfl+1), 0..32].into(), &file);
-        let FIRMWARE_VERSION: String = unpack_string("FIRMWARE_VERSION", STRING32, [pfl..(pfl+1), 0..32].into(), &file);
-        let WMO_INST_TYPE: String = unpack_string("WMO_INST_TYPE", STRING4, [pfl..(pfl+1), 0..4].into(), &file);
-        let JULD: f64 = file.variable("JULD").map(|var| var.get_value([pindex]).unwrap_or(999999.0)).unwrap_or(999999.0);
-        let JULD_QC: String = unpack_string("JULD_QC", STRING1, [pfl..(pfl+1)].into(), &file);
-        let JULD_LOCATION: f64 = file.variable("JULD_LOCATION").map(|var| var.get_value([pindex]).unwrap_or(999999.0)).unwrap_or(999999.0);
-        let mut LATITUDE: f64 = file.variable("LATITUDE").map(|var| var.get_value([pindex]).unwrap_or(99999.0)).unwrap_or(99999.0);
-        let mut LONGITUDE: f64 = file.variable("LONGITUDE").map(|var| var.get_value([pindex]).unwrap_or(99999.0)).unwrap_or(99999.0);
-        let latitude_fills = [99999.0, -99.999, -999.0];
-        let longitude_fills = [99999.0, -999.999, -999.0]; 
-        if latitude_fills.contains(&LATITUDE) || longitude_fills.contains(&LONGITUDE) || LATITUDE.is_nan() || LONGITUDE.is_nan() {
-            LATITUDE = -90.0;
-            LONGITUDE = 0.0;
+    match documents {
+        Documents::Profiles => {
+            let core = upsert(&collections.argo, &collections.argo_search);
+            let synthetic = upsert(&collections.argo_synthetic, &collections.argo_synthetic_search);
+            Ok(Arc::new(SplitSink::new(core, synthetic)))
         }
-        LONGITUDE = if LONGITUDE > 180.0 {
-            LONGITUDE - 360.0
-        } else if LONGITUDE < -180.0 {
-            LONGITUDE + 360.0
-        } else {
-            LONGITUDE
-        };
-        let POSITION_QC: String = unpack_string("POSITION_QC", STRING1, [pfl..(pfl+1)].into(), &file);
-        let POSITIONING_SYSTEM: String = unpack_string("POSITIONING_SYSTEM", STRING8, [pfl..(pfl+1), 0..8].into(), &file);
-        let VERTICAL_SAMPLING_SCHEME: String = unpack_string("VERTICAL_SAMPLING_SCHEME", STRING256, [pfl..(pfl+1), 0..256].into(), &file);
-        let CONFIG_MISSION_NUMBER: i32 = file.variable("CONFIG_MISSION_NUMBER").map(|var| var.get_value([pindex]).unwrap_or(99999)).unwrap_or(99999);
+        Documents::Merged => Ok(Arc::new(upsert(&collections.argo_merged, &collections.argo_merged_search))),
+    }
+}
 
-        let PARAMETER_DATA_MODE: Vec<String> = if let Some(_variable) = file.variable("PARAMETER_DATA_MODE") {
-            unpack_string_array("PARAMETER_DATA_MODE", STRING1, N_PARAM, [pfl..(pfl+1), 0..N_PARAM].into(), &file)
-        } else {
-            vec![DATA_MODE.clone(); STATION_PARAMETERS.len()]
-        };
-        
-        let mut realtime_data: Option<HashMap<String, Vec<f64>>> = STATION_PARAMETERS.iter()
-            .map(|param| {
-                if param.is_empty() {
-                    Ok((param.clone(), vec![]))
-                } else {
-                    match file.variable(param) {
-                        Some(variable) => {
-                            let mut data: Vec<f64> = variable.get_values([pfl..(pfl+1), 0..N_LEVELS])?;
-                            if let Some(pos) = data.iter().rposition(|&x| x != 99999.0) {
-                                data.truncate(pos + 1);
-                            }
-                            Ok((param.clone(), data))
-                        },
-                        None => Ok((param.clone(), vec![])),
-                    }
-                }
-            })
-            .collect::<Result<_, Box<dyn Error>>>()
-            .map(Some)
-            .unwrap_or(None);
-        if let Some(realtime_data) = &mut realtime_data {
-            realtime_data.retain(|_, v| !v.is_empty());
+// like upsert_sink, but batch-inserting into empty collections; both halves record into the one journal
+fn rebuild_sink(db: &Database, config: &Config, documents: Documents, batch_size: usize, journal: Option<Journal>, retry: RetryPolicy) -> Arc<dyn Sink> {
+    let collections = &config.collections;
+    let journal: Option<SharedJournal> = journal.map(|journal| Arc::new(tokio::sync::Mutex::new(journal)));
+    match documents {
+        Documents::Profiles => {
+            let core = MongoSink::rebuild(db.collection(&collections.argo), db.collection(&collections.argo_search), batch_size, journal.clone(), retry);
+            let synthetic = MongoSink::rebuild(db.collection(&collections.argo_synthetic), db.collection(&collections.argo_synthetic_search), batch_size, journal, retry);
+            Arc::new(SplitSink::new(core, synthetic))
         }
+        Documents::Merged => Arc::new(MongoSink::rebuild(db.collection(&collections.argo_merged), db.collection(&collections.argo_merged_search), batch_size, journal, retry)),
+    }
+}
 
-        let mut adjusted_data: Option<HashMap<String, Vec<f64>>> = STATION_PARAMETERS.iter()
-            .enumerate()
-            .map(|(i, param)| {
-                if param.is_empty() {
-                    Ok((param.clone(), vec![]))
-                } else {
-                    let data_mode = PARAMETER_DATA_MODE.get(i).cloned().unwrap_or(DATA_MODE.clone());
-                    if data_mode == "R" || param == "NB_SAMPLE_CTD" {
-                        Ok((param.clone(), vec![]))
-                    } else {
-                        let adjusted_variable_name = format!("{}_ADJUSTED", param);
-                        match file.variable(&adjusted_variable_name) {
-                            Some(variable) => {
-                                let mut data: Vec<f64> = variable.get_values([pfl..(pfl+1), 0..N_LEVELS])?;
-                                if let Some(pos) = data.iter().rposition(|&x| x != 99999.0) {
-                                    data.truncate(pos + 1);
-                                }
-                                Ok((param.clone(), data))
-                            },
-                            None => Ok((param.clone(), vec![])),
-                        }                    
-                    }
-                }
-            })
-            .collect::<Result<_, Box<dyn Error>>>()
-            .map(Some)
-            .unwrap_or(None);
-        if let Some(adjusted_data) = &mut adjusted_data {
-            adjusted_data.retain(|_, v| !v.is_empty());
-        }
+// store the run's summary in the runs collection. The document's _id is fixed up front, so an
+// attempt that went in before its reply was lost shows up as a duplicate key on the retry.
+async fn record_run(db: &Database, config: &Config, summary: &RunSummary, retry: RetryPolicy) -> Result<(), Error> {
+    let mut run = bson::to_document(summary)?;
+    run.insert("_id", bson::oid::ObjectId::new());
+    let runs = db.collection::<bson::Document>(&config.collections.runs);
+    let result = retry.run("Recording the run", || async { Ok(runs.insert_one(&run, None).await?) }).await;
+    match result {
+        Err(Error::Mongo(e)) if matches!(&*e.kind, ErrorKind::Write(WriteFailure::WriteError(err)) if err.code == DUPLICATE_KEY) => Ok(()),
+        result => result.map(|_| ()),
+    }
+}
 
-        let mut level_qc: Option<HashMap<String, Vec<String>>> = STATION_PARAMETERS.iter()
-            .map(|param| {
-                if param.is_empty() {
-                    Ok((param.clone(), vec![]))
-                } else {
-                    let qc_variable_name = format!("{}_QC", param);
-                    let mut qc_vec = unpack_string_array(&qc_variable_name, STRING1, N_LEVELS, [pfl..(pfl+1), 0..N_LEVELS].into(), &file);
-                    if let Some(pos) = qc_vec.iter().rposition(|x| x != "") {
-                        qc_vec.truncate(pos + 1);
-                    }
-                    Ok((param.clone(), qc_vec))
-                }
-            })
-            .collect::<Result<_, Box<dyn Error>>>()
-            .map(Some)
-            .unwrap_or(None);
-        if let Some(level_qc) = &mut level_qc {
-            level_qc.retain(|_, v| !v.is_empty() && !v.iter().all(|x| x == ""));
-        }
-            
-        let mut adjusted_level_qc: Option<HashMap<String, Vec<String>>> = STATION_PARAMETERS.iter()
-            .enumerate()
-            .map(|(i, param)| {
-                if param.is_empty() {
-                    Ok((param.clone(), vec![]))
-                } else {
-                    let data_mode = PARAMETER_DATA_MODE.get(i).cloned().unwrap_or(DATA_MODE.clone());
-                    if data_mode == "R" || param == "NB_SAMPLE_CTD" {
-                        Ok((param.clone(), vec![]))
-                    } else {
-                        let qc_variable_name = format!("{}_ADJUSTED_QC", param);
-                        let mut qc_vec = unpack_string_array(&qc_variable_name, STRING1, N_LEVELS, [pfl..(pfl+1), 0..N_LEVELS].into(), &file);
-                        if let Some(pos) = qc_vec.iter().rposition(|x| x != "") {
-                            qc_vec.truncate(pos + 1);
-                        }
-                        Ok((param.clone(), qc_vec))
-                    }
-                }
-            })
-            .collect::<Result<_, Box<dyn Error>>>()
-            .map(Some)
-            .unwrap_or(None);
-        if let Some(adjusted_level_qc) = &mut adjusted_level_qc {
-            adjusted_level_qc.retain(|_, v| !v.is_empty());
-        }
+// ingest the files named on the command line; returns the number of failures
+async fn run_ingest(args: IngestArgs, common: CommonArgs, config: &Config) -> Result<usize, Error> {
+    let mut summary = RunSummary::start(if args.merged { "merge" } else if args.rebuild { "rebuild" } else { "ingest" });
+    let documents = if args.merged { Documents::Merged } else { Documents::Profiles };
 
-        // make sure we didn't truncate too many fill values
-        // Find the maximum length among all vectors in the HashMaps
-        let max_len = realtime_data.as_ref().map_or(0, |m| m.values().map(|v| v.len()).max().unwrap_or(0))
-        .max(adjusted_data.as_ref().map_or(0, |m| m.values().map(|v| v.len()).max().unwrap_or(0)))
-        .max(level_qc.as_ref().map_or(0, |m| m.values().map(|v| v.len()).max().unwrap_or(0)))
-        .max(adjusted_level_qc.as_ref().map_or(0, |m| m.values().map(|v| v.len()).max().unwrap_or(0)));
-        // Pad vectors in realtime_data and adjusted_data with 99999.0
-        if let Some(realtime_data) = &mut realtime_data {
-            for vec in realtime_data.values_mut() {
-                vec.resize(max_len, 99999.0);
-            }
-        }
-        if let Some(adjusted_data) = &mut adjusted_data {
-            for vec in adjusted_data.values_mut() {
-                vec.resize(max_len, 99999.0);
-            }
-        }
-        // Pad vectors in level_qc and adjusted_level_qc with ""
-        if let Some(level_qc) = &mut level_qc {
-            for vec in level_qc.values_mut() {
-                vec.resize(max_len, "".to_string());
-            }
+    // Collect the file names of interest from the command line and any file lists
+    let mut filenames: Vec<FileEntry> = args.files.into_iter().map(FileEntry::listed).collect();
+    if let Some(files_from) = &args.files_from {
+        let lines = if files_from == "-" {
+            read_file_list(io::stdin().lock())?
+        } else {
+            read_file_list(BufReader::new(File::open(files_from)?))?
+        };
+        filenames.extend(lines.iter().filter_map(|line| rsync_entry_path(line, &common.rsync_root)));
+    }
+    if let Some(root) = &args.walk {
+        let filter = WalkFilter {
+            dacs: args.dacs.clone(),
+            wmo_min: args.wmo_min,
+            wmo_max: args.wmo_max,
+            prefixes: args.prefixes.clone(),
+        };
+        let walked = walk::walk_mirror(root, &filter)?;
+        let mut skip_counts: BTreeMap<walk::SkipReason, usize> = BTreeMap::new();
+        for (path, reason) in &walked.skipped {
+            eprintln!("Skipping {}: {}", path.display(), reason);
+            *skip_counts.entry(*reason).or_insert(0) += 1;
         }
-        if let Some(adjusted_level_qc) = &mut adjusted_level_qc {
-            for vec in adjusted_level_qc.values_mut() {
-                vec.resize(max_len, "".to_string());
-            }
+        eprintln!("Found {} profile files under {}", walked.files.len(), root.display());
+        for (reason, count) in skip_counts {
+            eprintln!("Skipped {} entries: {}", count, reason);
         }
+        summary.skipped += walked.skipped.len();
+        filenames.extend(walked.files.into_iter().map(FileEntry::listed));
+    }
+    if documents == Documents::Merged {
+        // each B-file brings in its own core file when it is processed
+        let before = filenames.len();
+        filenames = merge::bgc_entries(filenames);
+        eprintln!("Skipping {} entries that are repeats or have no B-file", before - filenames.len());
+        summary.skipped += before - filenames.len();
+    }
+    if filenames.is_empty() {
+        eprintln!("No files to process; pass file names as arguments, or use --files-from or --walk");
+        std::process::exit(1);
+    }
 
-        let mut data_info: Option<HashMap<String, DataInfo>> = STATION_PARAMETERS.iter()
-            .enumerate()
-            .map(|(i, param)| {
-                if param.is_empty() || param == "NB_SAMPLE_CTD" {
-                    Ok((param.clone(), DataInfo {
-                        DATA_MODE: "".to_string(),
-                        UNITS: "".to_string(),
-                        LONG_NAME: "".to_string(),
-                        PROFILE_PARAMETER_QC: "".to_string(),
-                    }))
-                } else {
-                    // assumption: if PARAMETER_DATA_MODE exists, it should be used in lieu of DATA_MODE
-                    let data_mode = PARAMETER_DATA_MODE.get(i).cloned().unwrap_or(DATA_MODE.clone());
-                    match file.variable(param) {
-                        Some(variable) => {
-                            //let data_mode = PARAMETER_DATA_MODE.get(i).cloned().unwrap_or(DATA_MODE.clone());
-                            let units = variable.attribute_value("units").unwrap()?;
-                            let long_name = variable.attribute_value("long_name").unwrap()?;
-                            let qc_variable_name = format!("PROFILE_{}_QC", param);
-                            let qc_value = unpack_string(&qc_variable_name, STRING1, [pfl..(pfl+1)].into(), &file);
-                            if let netcdf::AttributeValue::Str(u) = units {
-                                if let netcdf::AttributeValue::Str(l) = long_name {
-                                    Ok((param.clone(), DataInfo {
-                                        DATA_MODE: data_mode,
-                                        UNITS: u.to_string(),
-                                        LONG_NAME: l.to_string(),
-                                        PROFILE_PARAMETER_QC: qc_value,
-                                    }))
-                                } else {
-                                    Err("Could not extract long_name attribute".into())
-                                }
-                            } else {
-                                Err("Could not extract units attribute".into())
-                            } 
-                        },
-                        None => Ok((param.clone(), DataInfo {
-                            DATA_MODE: "".to_string(),
-                            UNITS: "".to_string(),
-                            LONG_NAME: "".to_string(),
-                            PROFILE_PARAMETER_QC: "".to_string(),
-                        })),
-                    }   
-                }
-            })
-            .collect::<Result<_, Box<dyn Error>>>()
-            .map(Some)
-            .unwrap_or(None);
-        if let Some(data_info) = &mut data_info {
-            data_info.remove("");
-        }
+    if let Some(sink) = common.ndjson_sink(config, documents)? {
+        let summary = ingest(filenames, common.concurrency(), documents, sink, None, config, summary).await?;
+        return Ok(summary.failure_count);
+    }
 
-        // let adjusted_level_error: HashMap<String, Vec<f64>> = STATION_PARAMETERS.iter()
-        //     .map(|param| {
-        //         let adjusted_variable_name = format!("{}_ADJUSTED_ERROR", param);
-        //         let variable = file.variable(&adjusted_variable_name).expect(&format!("Could not find variable '{}'", adjusted_variable_name));
-        //         let data: Vec<f64> = variable.get_values([..1, ..N_LEVELS])?;
-        //         Ok((param.clone(), data))
-        //     })
-        //     .collect::<Result<_, Box<dyn Error>>>()?;
-        
-        // construct the structs for this file ///////////////////////////////
-    
-        let data_object = DataSchema {
-            _id: format!("{}_{}", id, pfl),
-            geolocation: GeoJSONPoint {
-                location_type: "Point".to_string(),
-                coordinates: [LONGITUDE, LATITUDE],
-            },
-            CYCLE_NUMBER: CYCLE_NUMBER,
-            DIRECTION: DIRECTION,
-            DATA_STATE_INDICATOR: DATA_STATE_INDICATOR,
-            DATA_MODE: DATA_MODE,
-            DATE_CREATION: DATE_CREATION,
-            DATE_UPDATE: DATE_UPDATE,
-            DC_REFERENCE: DC_REFERENCE,
-            JULD: JULD,
-            JULD_QC: JULD_QC,
-            JULD_LOCATION: JULD_LOCATION,
-            POSITION_QC: POSITION_QC,
-            VERTICAL_SAMPLING_SCHEME: VERTICAL_SAMPLING_SCHEME,
-            CONFIG_MISSION_NUMBER: CONFIG_MISSION_NUMBER,
-            STATION_PARAMETERS: STATION_PARAMETERS.clone(),
-            realtime_data: realtime_data,
-            adjusted_data: adjusted_data,
-            data_info: data_info,
-            level_qc: level_qc,
-            adjusted_level_qc: adjusted_level_qc,
-            DATA_TYPE: DATA_TYPE,
-            FORMAT_VERSION: FORMAT_VERSION,
-            HANDBOOK_VERSION: HANDBOOK_VERSION,
-            REFERENCE_DATE_TIME: REFERENCE_DATE_TIME,
-            PROJECT_NAME: PROJECT_NAME,
-            PI_NAME: split_string(PI_NAME, ','),
-            DATA_CENTRE: DATA_CENTRE,
-            PLATFORM_TYPE: PLATFORM_TYPE,
-            PLATFORM_NUMBER: PLATFORM_NUMBER,
-            FLOAT_SERIAL_NO: FLOAT_SERIAL_NO,
-            FIRMWARE_VERSION: FIRMWARE_VERSION,
-            WMO_INST_TYPE: WMO_INST_TYPE,
-            POSITIONING_SYSTEM: POSITIONING_SYSTEM,
-            source_file: source_file.clone(),
-        };
+    let client = connect(config).await?;
+    let db = config.database(&client);
 
-        let map_object = MapSchema {
-            _id: format!("{}_{}", id, pfl),
-            geolocation: GeoJSONPoint {
-                location_type: "Point".to_string(),
-                coordinates: [LONGITUDE, LATITUDE],
-            },
-            JULD: JULD,
-            STATION_PARAMETERS: STATION_PARAMETERS,
-            source_file: source_file.clone(),
-        };
+    // resuming a rebuild: skip whatever the journal says is already in the database
+    let mut journal = None;
+    let completed = if let Some(path) = &args.journal {
+        let (j, completed) = Journal::open_file(path)?;
+        journal = Some(j);
+        completed
+    } else if let Some(name) = &args.journal_collection {
+        let (j, completed) = Journal::open_collection(db.collection(name), common.retry_policy()).await?;
+        journal = Some(j);
+        completed
+    } else {
+        HashSet::new()
+    };
+    if !completed.is_empty() {
+        let before = filenames.len();
+        filenames.retain(|entry| match config.source.url(&entry.path) {
+            Ok(url) => !completed.contains(&url),
+            Err(_) => true,
+        });
+        eprintln!("Skipping {} files already recorded in the journal", before - filenames.len());
+        summary.skipped += before - filenames.len();
+    }
 
-        //println!("{:?}", data_object);
-    
-        // insert the structs into the database ////////////////////////////
-        let filter = doc! {
-            "_id": data_object._id.clone(),
-        };
-        let update = doc! {
-            "$set": bson::to_bson(&data_object)?,
-        };
-        let options = mongodb::options::UpdateOptions::builder().upsert(true).build();
-        argo.update_one(filter, update, options).await?;
+    let sink = if args.rebuild {
+        rebuild_sink(&db, config, documents, args.batch_size, journal, common.retry_policy())
+    } else {
+        upsert_sink(&db, config, documents, &common).await?
+    };
+    // ingest_failures holds files to retry as ordinary profiles, so merging failures stay out of it
+    let failure_log = match documents {
+        Documents::Profiles => Some(FailureLog::open(db.collection(&config.collections.ingest_failures), common.retry_policy()).await?),
+        Documents::Merged => None,
+    };
+    let summary = ingest(filenames, common.concurrency(), documents, sink, failure_log, config, summary).await?;
+    record_run(&db, config, &summary, common.retry_policy()).await?;
+    Ok(summary.failure_count)
+}
 
-        let map_filter = doc! {
-            "_id": map_object._id.clone(),
-        };
-        let map_update = doc! {
-            "$set": bson::to_bson(&map_object)?,
-        };
-        let map_options = mongodb::options::UpdateOptions::builder().upsert(true).build();
-        argo_search.update_one(map_filter, map_update, map_options).await?;
+// process the rsync updates recorded in the newest log directory; returns the number of failures
+async fn run_update(args: UpdateArgs, config: &Config) -> Result<usize, Error> {
+    let mut summary = RunSummary::start("update");
+    let log_dir = match update::latest_log_dir(&args.log_root)? {
+        Some(log_dir) => log_dir,
+        None => return Err(Error::Setup(format!("No rsync log directories found under {}", args.log_root.display()))),
+    };
+    if !args.force {
+        update::check_log_dir(&log_dir, Duration::from_secs(args.max_age_hours * 3600))?;
     }
-    
-    Ok(())
+    summary.log_dir = Some(log_dir.display().to_string());
+    let updates = log_dir.join("rsyncupdates");
+    eprintln!("Processing rsync updates from {}", updates.display());
+    let filenames: Vec<FileEntry> = read_file_list(BufReader::new(File::open(&updates)?))?
+        .iter()
+        .filter_map(|line| rsync_entry_path(line, &args.common.rsync_root))
+        .collect();
+
+    if let Some(sink) = args.common.ndjson_sink(config, Documents::Profiles)? {
+        let summary = ingest(filenames, args.common.concurrency(), Documents::Profiles, sink, None, config, summary).await?;
+        return Ok(summary.failure_count);
+    }
+
+    let client = connect(config).await?;
+    let db = config.database(&client);
+    let sink = upsert_sink(&db, config, Documents::Profiles, &args.common).await?;
+    let failure_log = FailureLog::open(db.collection(&config.collections.ingest_failures), args.common.retry_policy()).await?;
+    let summary = ingest(filenames, args.common.concurrency(), Documents::Profiles, sink, Some(failure_log), config, summary).await?;
+    record_run(&db, config, &summary, args.common.retry_policy()).await?;
+
+    // individual failed files are reported above; the directory as a whole is done
+    update::mark_processed(&log_dir)?;
+    Ok(summary.failure_count)
 }
 
+// reprocess the files in the ingest_failures collection; returns the number that failed again
+async fn run_retry_failures(args: RetryArgs, config: &Config) -> Result<usize, Error> {
+    let summary = RunSummary::start("retry-failures");
+    let client = connect(config).await?;
+    let db = config.database(&client);
+    let failure_log = FailureLog::open(db.collection(&config.collections.ingest_failures), args.common.retry_policy()).await?;
+    let filenames: Vec<FileEntry> = failure_log.filenames(&args.categories).await?.into_iter().map(FileEntry::listed).collect();
+    eprintln!("Retrying {} failed files", filenames.len());
 
+    // a dry run leaves the failure records alone too
+    if let Some(sink) = args.common.ndjson_sink(config, Documents::Profiles)? {
+        let summary = ingest(filenames, args.common.concurrency(), Documents::Profiles, sink, None, config, summary).await?;
+        return Ok(summary.failure_count);
+    }
+
+    let sink = upsert_sink(&db, config, Documents::Profiles, &args.common).await?;
+    let summary = ingest(filenames, args.common.concurrency(), Documents::Profiles, sink, Some(failure_log), config, summary).await?;
+    record_run(&db, config, &summary, args.common.retry_policy()).await?;
+    Ok(summary.failure_count)
+}
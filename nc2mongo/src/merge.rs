@@ -0,0 +1,226 @@
+// join a B-file's profiles with those of the core file for the same cycle, so every
+// parameter of a profile is in one document
+use crate::error::Error;
+use crate::file_list::FileEntry;
+use crate::parse::{parse_file, SourceFileVersion};
+use crate::schema::{DataSchema, LevelValues, ProfileDoc};
+use crate::walk::{self, FilePrefix};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+// the core file alongside a B-file, e.g. D1901727_357.nc for BR1901727_357.nc;
+// a delayed mode core file is preferred over a realtime one
+pub fn core_file_for(bgc_filename: &str) -> Option<String> {
+    let path = Path::new(bgc_filename);
+    let name = path.file_name()?.to_str()?;
+    let rest = name.strip_prefix("BD").or_else(|| name.strip_prefix("BR"))?;
+    ["D", "R"].iter()
+        .map(|prefix| path.with_file_name(format!("{}{}", prefix, rest)))
+        .find(|core| core.exists())
+        .map(|core| core.to_string_lossy().into_owned())
+}
+
+// the B-file alongside a core file, e.g. BR1901727_357.nc for D1901727_357.nc
+pub fn bgc_file_for(core_filename: &str) -> Option<String> {
+    let path = Path::new(core_filename);
+    let name = path.file_name()?.to_str()?;
+    let rest = name.strip_prefix('D').or_else(|| name.strip_prefix('R'))?;
+    ["BD", "BR"].iter()
+        .map(|prefix| path.with_file_name(format!("{}{}", prefix, rest)))
+        .find(|bgc| bgc.exists())
+        .map(|bgc| bgc.to_string_lossy().into_owned())
+}
+
+// the B-files whose merged documents the given files affect: each B-file itself, and the
+// B-file alongside each core file, since a core file that changes, or moves from R to D,
+// changes those documents too. Each B-file comes once; files without one are dropped.
+pub fn bgc_entries(entries: Vec<FileEntry>) -> Vec<FileEntry> {
+    let mut seen = HashSet::new();
+    entries.into_iter()
+        .filter_map(|entry| match file_prefix(&entry.path) {
+            Some(prefix) if prefix.is_bgc() => Some(entry),
+            Some(prefix) if prefix.is_core() => bgc_file_for(&entry.path).map(FileEntry::listed),
+            _ => None,
+        })
+        .filter(|entry| seen.insert(entry.path.clone()))
+        .collect()
+}
+
+fn file_prefix(filename: &str) -> Option<FilePrefix> {
+    let name = Path::new(filename).file_name()?.to_str()?;
+    walk::parse_profile_name(name).map(|(prefix, _)| prefix)
+}
+
+// stands for both files, so a merged document counts as changed when either file does
+pub fn merged_version(bgc: &SourceFileVersion, core: &SourceFileVersion) -> SourceFileVersion {
+    let mut hasher = Sha256::new();
+    hasher.update(&bgc.sha256);
+    hasher.update(&core.sha256);
+    SourceFileVersion {
+        sha256: format!("{:x}", hasher.finalize()),
+        size: bgc.size + core.size,
+        mtime: bgc.mtime.clone().max(core.mtime.clone()),
+    }
+}
+
+// parse a B-file and its core file and merge them profile by profile; the merged
+// documents belong to the B-file's source_file
+pub fn parse_merged_file(
+    bgc_filename: &str,
+    source_file: &str,
+    core_filename: &str,
+    core_source_file: &str,
+    version: &SourceFileVersion,
+) -> Result<Vec<ProfileDoc>, Error> {
+    let core = parse_file(core_filename, core_source_file, version)?;
+    let bgc = parse_file(bgc_filename, source_file, version)?;
+    merge_profiles(core, bgc)
+}
+
+// a B-file holds the same profiles as its core file, in the same order
+pub fn merge_profiles(core: Vec<ProfileDoc>, bgc: Vec<ProfileDoc>) -> Result<Vec<ProfileDoc>, Error> {
+    if core.len() != bgc.len() {
+        let source_file = bgc.first().map(|profile| profile.data.source_file.clone()).unwrap_or_default();
+        return Err(Error::Merge(format!("{} has {} profiles but its core file has {}", source_file, bgc.len(), core.len())));
+    }
+    core.into_iter().zip(bgc).map(|(core, bgc)| merge_profile(core, bgc)).collect()
+}
+
+// the core profile with the B-file's parameters added; where both files have a
+// parameter, PRES in particular, the core file's values are kept
+fn merge_profile(core: ProfileDoc, bgc: ProfileDoc) -> Result<ProfileDoc, Error> {
+    let ProfileDoc { mut data, mut search } = core;
+    let bgc = bgc.data;
+    if (&data.PLATFORM_NUMBER, data.CYCLE_NUMBER, &data.DIRECTION) != (&bgc.PLATFORM_NUMBER, bgc.CYCLE_NUMBER, &bgc.DIRECTION) {
+        return Err(Error::Merge(format!(
+            "{} is platform {} cycle {} direction {}, but {} is platform {} cycle {} direction {}",
+            bgc._id, bgc.PLATFORM_NUMBER, bgc.CYCLE_NUMBER, bgc.DIRECTION,
+            data._id, data.PLATFORM_NUMBER, data.CYCLE_NUMBER, data.DIRECTION,
+        )));
+    }
+    check_pres(&data, &bgc)?;
+
+    // R1901727_357D_0 and BD1901727_357D_0 become 1901727_357D_0
+    let id = data._id.trim_start_matches(|c: char| c.is_ascii_alphabetic()).to_string();
+    for param in &bgc.STATION_PARAMETERS {
+        if !data.STATION_PARAMETERS.contains(param) {
+            data.STATION_PARAMETERS.push(param.clone());
+        }
+    }
+    add_missing(&mut data.realtime_data, bgc.realtime_data);
+    add_missing(&mut data.adjusted_data, bgc.adjusted_data);
+    add_missing(&mut data.data_info, bgc.data_info);
+    add_missing(&mut data.level_qc, bgc.level_qc);
+    add_missing(&mut data.adjusted_level_qc, bgc.adjusted_level_qc);
+    data.DATE_UPDATE = data.DATE_UPDATE.max(bgc.DATE_UPDATE);
+    data.core_source_file = Some(std::mem::replace(&mut data.source_file, bgc.source_file));
+    data._id = id.clone();
+
+    search._id = id;
+    search.STATION_PARAMETERS = data.STATION_PARAMETERS.clone();
+    search.source_file = data.source_file.clone();
+    Ok(ProfileDoc { data, search })
+}
+
+fn add_missing<V>(merged: &mut Option<HashMap<String, V>>, other: Option<HashMap<String, V>>) {
+    match (merged.as_mut(), other) {
+        (Some(merged), Some(other)) => {
+            for (param, values) in other {
+                merged.entry(param).or_insert(values);
+            }
+        }
+        (None, Some(other)) => *merged = Some(other),
+        (_, None) => {}
+    }
+}
+
+fn pres(data: &DataSchema) -> Option<&LevelValues> {
+    data.realtime_data.as_ref().and_then(|realtime_data| realtime_data.get("PRES"))
+}
+
+// the B-file's levels must be the core file's levels, or its values would be
+// attached to the wrong pressures
+fn check_pres(core: &DataSchema, bgc: &DataSchema) -> Result<(), Error> {
+    let conflict = |reason: String| Error::PresConflict { profile: core._id.clone(), reason };
+    match (pres(core), pres(bgc)) {
+        (Some(LevelValues::Scalar(core_pres)), Some(LevelValues::Scalar(bgc_pres))) => {
+            let levels = core_pres.len().max(bgc_pres.len());
+            match (0..levels).find(|&level| core_pres.get(level) != bgc_pres.get(level)) {
+                None => Ok(()),
+                Some(level) => match (core_pres.get(level), bgc_pres.get(level)) {
+                    (Some(core_value), Some(bgc_value)) => Err(conflict(format!("level {} is {} in the core file but {} in the B-file", level, core_value, bgc_value))),
+                    _ => Err(conflict(format!("the core file has {} levels but the B-file has {}", core_pres.len(), bgc_pres.len()))),
+                },
+            }
+        }
+        _ => Err(conflict("one of the files has no PRES".to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    // a profiles directory holding empty files with the given names
+    fn profiles_dir(names: &[&str]) -> TempDir {
+        let dir = TempDir::new().unwrap();
+        for name in names {
+            fs::write(dir.path().join(name), b"").unwrap();
+        }
+        dir
+    }
+
+    fn path(dir: &Path, name: &str) -> String {
+        dir.join(name).to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn the_delayed_mode_core_file_is_preferred() {
+        let dir = profiles_dir(&["BR1901727_357.nc", "R1901727_357.nc", "D1901727_357.nc", "BD1901727_358D.nc", "R1901727_358D.nc", "BR1901727_359.nc"]);
+        let found = (
+            core_file_for(&path(dir.path(), "BR1901727_357.nc")),
+            core_file_for(&path(dir.path(), "BD1901727_358D.nc")),
+            core_file_for(&path(dir.path(), "BR1901727_359.nc")),
+            core_file_for(&path(dir.path(), "R1901727_357.nc")),
+        );
+        assert_eq!(found.0, Some(path(dir.path(), "D1901727_357.nc")));
+        assert_eq!(found.1, Some(path(dir.path(), "R1901727_358D.nc")));
+        assert_eq!(found.2, None);
+        assert_eq!(found.3, None);
+    }
+
+    #[test]
+    fn core_files_stand_for_the_b_file_alongside() {
+        let dir = profiles_dir(&["BR1901727_357.nc", "D1901727_357.nc", "BD1901727_358.nc", "R1901727_358.nc", "R1901727_359.nc", "SD1901727_358.nc"]);
+        let entries = vec![
+            FileEntry::listed(path(dir.path(), "D1901727_357.nc")),
+            // R moved to D: both lines lead back to the B-file
+            FileEntry { path: path(dir.path(), "R1901727_357.nc"), deleted: true },
+            FileEntry::listed(path(dir.path(), "BR1901727_357.nc")),
+            FileEntry::listed(path(dir.path(), "R1901727_358.nc")),
+            // no B-file for this cycle
+            FileEntry::listed(path(dir.path(), "R1901727_359.nc")),
+            FileEntry::listed(path(dir.path(), "SD1901727_358.nc")),
+            FileEntry { path: path(dir.path(), "BD1901727_360.nc"), deleted: true },
+        ];
+        let bgc = bgc_entries(entries);
+        assert_eq!(bgc, [
+            FileEntry::listed(path(dir.path(), "BR1901727_357.nc")),
+            FileEntry::listed(path(dir.path(), "BD1901727_358.nc")),
+            FileEntry { path: path(dir.path(), "BD1901727_360.nc"), deleted: true },
+        ]);
+    }
+
+    #[test]
+    fn the_merged_version_changes_with_either_file() {
+        let version = |sha256: &str| SourceFileVersion { sha256: sha256.to_string(), size: 10, mtime: "2024-03-02T00:00:00+00:00".to_string() };
+        let merged = merged_version(&version("b"), &version("core"));
+        assert_eq!(merged.size, 20);
+        assert_eq!(merged.sha256, merged_version(&version("b"), &version("core")).sha256);
+        assert_ne!(merged.sha256, merged_version(&version("b"), &version("new core")).sha256);
+        assert_ne!(merged.sha256, merged_version(&version("new b"), &version("core")).sha256);
+    }
+}
@@ -0,0 +1,512 @@
+// decode Argo profile netCDF files into the documents we store
+use netcdf;
+use crate::config::Source;
+use crate::error::Error;
+use crate::schema::{DataInfo, DataSchema, GeoJSONPoint, LevelValues, MapSchema, ProfileDoc, SamplingScheme};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+// helper functions ///////////////////////////////////////////
+
+fn trim_null_bytes(input: String) -> String {
+    input.trim().trim_end_matches('\0').to_string()
+}
+
+fn unpack_string(name: &str, buflen: usize, extents: netcdf::Extents, file: &netcdf::File) -> String {
+    let mut dump = vec![0_u8; buflen];
+    if let Some(variable) = file.variable(name) {
+        if let Ok(_) = variable.get_raw_values(&mut dump, extents) {
+            if let Ok(string) = String::from_utf8(dump) {
+                return trim_null_bytes(string);
+            }
+        }
+    }
+    String::new()
+}
+
+fn unpack_string_array(name: &str, buflen: usize, arraydim: usize, extents: netcdf::Extents, file: &netcdf::File) -> Vec<String> {
+    let mut dump = vec![0_u8; buflen * arraydim];
+    if let Some(variable) = file.variable(name) {
+        if let Ok(_) = variable.get_raw_values(&mut dump, extents) {
+            let strings: Vec<String> = dump
+                .chunks_exact(buflen)
+                .map(|chunk| {
+                    let string: String = String::from_utf8_lossy(chunk).into_owned().parse().unwrap_or_default();
+                    string.trim().to_string(); // Strip leading and trailing whitespace
+                    trim_null_bytes(string)
+                })
+                .collect();
+            return strings;
+        }
+    }
+    vec![String::new(); arraydim]
+}
+
+// like unpack_string_array, for strings a profile can't do without: an unreadable
+// variable or a string that isn't UTF-8 fails the file rather than becoming "" or mojibake
+fn decode_string_array(name: &str, buflen: usize, arraydim: usize, extents: netcdf::Extents, file: &netcdf::File) -> Result<Vec<String>, Error> {
+    let variable = file.variable(name).ok_or_else(|| Error::MissingVariable(name.to_string()))?;
+    let mut dump = vec![0_u8; buflen * arraydim];
+    variable.get_raw_values(&mut dump, extents)?;
+    dump.chunks_exact(buflen)
+        .map(|chunk| match std::str::from_utf8(chunk) {
+            Ok(string) => Ok(trim_null_bytes(string.to_string())),
+            Err(e) => Err(Error::StringDecode { variable: name.to_string(), reason: e.to_string() }),
+        })
+        .collect()
+}
+
+// one profile's values of a per-level variable, up to the last one that isn't fill;
+// empty if the file has no such variable
+fn unpack_levels(name: &str, pfl: usize, N_LEVELS: usize, file: &netcdf::File) -> Result<Vec<f64>, Error> {
+    match file.variable(name) {
+        Some(variable) => {
+            let mut data: Vec<f64> = variable.get_values([pfl..(pfl+1), 0..N_LEVELS])?;
+            if let Some(pos) = data.iter().rposition(|&x| x != 99999.0) {
+                data.truncate(pos + 1);
+            }
+            Ok(data)
+        },
+        None => Ok(vec![]),
+    }
+}
+
+// the N_VALUES<k> dimension of a parameter with an array of values at each level, and its length
+fn values_dimension(variable: &netcdf::Variable) -> Option<(String, usize)> {
+    variable.dimensions()
+        .get(2)
+        .filter(|dimension| dimension.name().starts_with("N_VALUES") && dimension.len() > 0)
+        .map(|dimension| (dimension.name(), dimension.len()))
+}
+
+// like unpack_levels, but a parameter with an N_VALUES dimension, such as a spectrum,
+// gets an array per level, up to the last level that isn't all fill
+fn unpack_param_levels(name: &str, pfl: usize, N_LEVELS: usize, file: &netcdf::File) -> Result<LevelValues, Error> {
+    match file.variable(name) {
+        Some(variable) => match values_dimension(&variable) {
+            Some((_, n_values)) => {
+                let data: Vec<f64> = variable.get_values([pfl..(pfl+1), 0..N_LEVELS, 0..n_values])?;
+                let mut levels: Vec<Vec<f64>> = data.chunks_exact(n_values).map(|level| level.to_vec()).collect();
+                if let Some(pos) = levels.iter().rposition(|level| level.iter().any(|&x| x != 99999.0)) {
+                    levels.truncate(pos + 1);
+                }
+                Ok(LevelValues::Array(levels))
+            },
+            None => Ok(LevelValues::Scalar(unpack_levels(name, pfl, N_LEVELS, file)?)),
+        },
+        None => Ok(LevelValues::Scalar(vec![])),
+    }
+}
+
+fn dimension_len(name: &str, file: &netcdf::File) -> Result<usize, Error> {
+    file.dimension(name)
+        .map(|dimension| dimension.len())
+        .ok_or_else(|| Error::MissingDimension(name.to_string()))
+}
+
+fn string_attribute(variable: &netcdf::Variable, attribute: &str) -> Result<String, Error> {
+    match variable.attribute_value(attribute) {
+        Some(Ok(netcdf::AttributeValue::Str(value))) => Ok(value),
+        Some(Ok(_)) => Err(Error::BadAttributeType { variable: variable.name(), attribute: attribute.to_string() }),
+        Some(Err(e)) => Err(e.into()),
+        None => Err(Error::MissingAttribute { variable: variable.name(), attribute: attribute.to_string() }),
+    }
+}
+
+fn split_string(input: String, separator: char) -> Vec<String> {
+    input.split(separator).map(|s| s.trim().to_string()).collect()
+}
+
+// identifies the exact contents of a source file, stored with each of its documents
+#[derive(Clone, Debug)]
+pub struct SourceFileVersion {
+    pub sha256: String,
+    pub size: u64,
+    pub mtime: String,
+}
+
+impl SourceFileVersion {
+    pub fn read(filename: &str) -> io::Result<SourceFileVersion> {
+        let mut file = File::open(filename)?;
+        let metadata = file.metadata()?;
+        let mut hasher = Sha256::new();
+        io::copy(&mut file, &mut hasher)?;
+        Ok(SourceFileVersion {
+            sha256: format!("{:x}", hasher.finalize()),
+            size: metadata.len(),
+            mtime: chrono::DateTime::<chrono::Utc>::from(metadata.modified()?).to_rfc3339(),
+        })
+    }
+}
+
+// read every profile in the netCDF file at path. Paths under a GDAC mirror get the
+// same upstream source_file as nc2mongo gives them; anything else keeps its path.
+pub fn parse_profile_file(path: &Path) -> Result<Vec<ProfileDoc>, Error> {
+    let filename = path.to_string_lossy();
+    let source_file = Source::default().url(&filename).unwrap_or_else(|_| filename.to_string());
+    let version = SourceFileVersion::read(&filename)?;
+    parse_file(&filename, &source_file, &version)
+}
+
+// unpack every profile in one netCDF file into the documents we store for it
+pub fn parse_file(filename: &str, source_file: &str, version: &SourceFileVersion) -> Result<Vec<ProfileDoc>, Error> {
+
+    let id = filename
+        .rsplit('/')
+        .next()
+        .and_then(|name| name.strip_suffix(".nc"))
+        .unwrap_or("");
+    let file = netcdf::open(filename)?;
+
+    let mut profiles = Vec::with_capacity(1);
+    // sinks keep first_ingested from any earlier version they already hold
+    let now = bson::DateTime::now();
+    // loop over internal profiles
+    let N_PROF: usize = dimension_len("N_PROF", &file)?;
+    for pfl in 0..N_PROF {
+
+        // data unpacking /////////////////////////////////////////////
+        let STRING1: usize = 1;
+        let STRING2: usize = 2;
+        let STRING4: usize = 4;
+        let STRING8: usize = 8;
+        let STRING16: usize = 16;
+        let STRING32: usize = 32;
+        let STRING64: usize = 64;
+        let STRING256: usize = 256;
+        let DATE_TIME: usize = 14;
+        
+        let N_PARAM: usize = dimension_len("N_PARAM", &file)?;
+        let N_LEVELS: usize = dimension_len("N_LEVELS", &file)?;
+        //let N_CALIB: usize = file.dimension("N_CALIB").unwrap().len();
+        //let N_HISTORY: usize = file.dimension("N_HISTORY").unwrap().len();
+    
+        let DATA_TYPE: String = unpack_string("DATA_TYPE", STRING16, [..16].into(), &file);
+        let FORMAT_VERSION: String = unpack_string("FORMAT_VERSION", STRING4, [..4].into(), &file);
+        let HANDBOOK_VERSION: String = unpack_string("HANDBOOK_VERSION", STRING4, [..4].into(), &file);
+        let REFERENCE_DATE_TIME: String = unpack_string("REFERENCE_DATE_TIME", DATE_TIME, [..14].into(), &file);
+        let DATE_CREATION: String = unpack_string("DATE_CREATION", DATE_TIME, [..14].into(), &file);
+        // DATE_UPDATE decides whether a file replaces what is stored, so it has to be readable
+        let DATE_UPDATE: String = decode_string_array("DATE_UPDATE", DATE_TIME, 1, [..14].into(), &file)?.remove(0);
+        let PLATFORM_NUMBER: String = unpack_string("PLATFORM_NUMBER", STRING8, [pfl..(pfl+1), 0..8].into(), &file);
+        let PROJECT_NAME: String = unpack_string("PROJECT_NAME", STRING64, [pfl..(pfl+1), 0..64].into(), &file);
+        let PI_NAME: String = unpack_string("PI_NAME", STRING64, [pfl..(pfl+1), 0..64].into(), &file);
+        let namesize: usize = file.variable("STATION_PARAMETERS")
+            .ok_or_else(|| Error::MissingVariable("STATION_PARAMETERS".to_string()))?
+            .dimensions()
+            .get(2)
+            .map(|dimension| dimension.len())
+            .ok_or_else(|| Error::MissingDimension("STATION_PARAMETERS name length".to_string()))?;
+        // parameter names are looked up as variables, so a garbled one can't be stored as is
+        let STATION_PARAMETERS: Vec<String> = decode_string_array(
+            "STATION_PARAMETERS",
+            namesize,
+            N_PARAM,
+            [pfl..(pfl+1), 0..N_PARAM, 0..namesize].into(),
+            &file,
+        )?;
+        let CYCLE_NUMBER: i32 = file.variable("CYCLE_NUMBER").map(|var| var.get_value([pfl]).unwrap_or(99999)).unwrap_or(99999);
+        let DIRECTION: String = unpack_string("DIRECTION", STRING1, [pfl..(pfl+1)].into(), &file);
+        let DATA_CENTRE: String = unpack_string("DATA_CENTRE", STRING2, [pfl..(pfl+1), 0..2].into(), &file);
+        let DC_REFERENCE: String = unpack_string("DC_REFERENCE", STRING32, [pfl..(pfl+1), 0..32].into(), &file);
+        let DATA_STATE_INDICATOR: String = unpack_string("DATA_STATE_INDICATOR", STRING4, [pfl..(pfl+1), 0..4].into(), &file);
+        let DATA_MODE: String = unpack_string("DATA_MODE", STRING1, [pfl..(pfl+1)].into(), &file);
+        let PLATFORM_TYPE: String = unpack_string("PLATFORM_TYPE", STRING32, [pfl..(pfl+1), 0..32].into(), &file);
+        let FLOAT_SERIAL_NO: String = unpack_string("FLOAT_SERIAL_NO", STRING32, [pfl..(pfl+1), 0..32].into(), &file);
+        let FIRMWARE_VERSION: String = unpack_string("FIRMWARE_VERSION", STRING32, [pfl..(pfl+1), 0..32].into(), &file);
+        let WMO_INST_TYPE: String = unpack_string("WMO_INST_TYPE", STRING4, [pfl..(pfl+1), 0..4].into(), &file);
+        let JULD: f64 = file.variable("JULD").map(|var| var.get_value([pfl]).unwrap_or(999999.0)).unwrap_or(999999.0);
+        let JULD_QC: String = unpack_string("JULD_QC", STRING1, [pfl..(pfl+1)].into(), &file);
+        let JULD_LOCATION: f64 = file.variable("JULD_LOCATION").map(|var| var.get_value([pfl]).unwrap_or(999999.0)).unwrap_or(999999.0);
+        let mut LATITUDE: f64 = file.variable("LATITUDE").map(|var| var.get_value([pfl]).unwrap_or(99999.0)).unwrap_or(99999.0);
+        let mut LONGITUDE: f64 = file.variable("LONGITUDE").map(|var| var.get_value([pfl]).unwrap_or(99999.0)).unwrap_or(99999.0);
+        let latitude_fills = [99999.0, -99.999, -999.0];
+        let longitude_fills = [99999.0, -999.999, -999.0]; 
+        if latitude_fills.contains(&LATITUDE) || longitude_fills.contains(&LONGITUDE) || LATITUDE.is_nan() || LONGITUDE.is_nan() {
+            LATITUDE = -90.0;
+            LONGITUDE = 0.0;
+        }
+        LONGITUDE = if LONGITUDE > 180.0 {
+            LONGITUDE - 360.0
+        } else if LONGITUDE < -180.0 {
+            LONGITUDE + 360.0
+        } else {
+            LONGITUDE
+        };
+        let POSITION_QC: String = unpack_string("POSITION_QC", STRING1, [pfl..(pfl+1)].into(), &file);
+        let POSITIONING_SYSTEM: String = unpack_string("POSITIONING_SYSTEM", STRING8, [pfl..(pfl+1), 0..8].into(), &file);
+        let VERTICAL_SAMPLING_SCHEME: String = unpack_string("VERTICAL_SAMPLING_SCHEME", STRING256, [pfl..(pfl+1), 0..256].into(), &file);
+        let sampling_scheme = SamplingScheme::parse(&VERTICAL_SAMPLING_SCHEME);
+        let CONFIG_MISSION_NUMBER: i32 = file.variable("CONFIG_MISSION_NUMBER").map(|var| var.get_value([pfl]).unwrap_or(99999)).unwrap_or(99999);
+
+        let PARAMETER_DATA_MODE: Vec<String> = if let Some(_variable) = file.variable("PARAMETER_DATA_MODE") {
+            unpack_string_array("PARAMETER_DATA_MODE", STRING1, N_PARAM, [pfl..(pfl+1), 0..N_PARAM].into(), &file)
+        } else {
+            vec![DATA_MODE.clone(); STATION_PARAMETERS.len()]
+        };
+        
+        let mut realtime_data: Option<HashMap<String, LevelValues>> = STATION_PARAMETERS.iter()
+            .map(|param| {
+                if param.is_empty() {
+                    Ok((param.clone(), LevelValues::Scalar(vec![])))
+                } else {
+                    Ok((param.clone(), unpack_param_levels(param, pfl, N_LEVELS, &file)?))
+                }
+            })
+            .collect::<Result<_, Error>>()
+            .map(Some)?;
+        if let Some(realtime_data) = &mut realtime_data {
+            realtime_data.retain(|_, v| !v.is_empty());
+        }
+
+        let mut adjusted_data: Option<HashMap<String, LevelValues>> = STATION_PARAMETERS.iter()
+            .enumerate()
+            .map(|(i, param)| {
+                if param.is_empty() {
+                    Ok((param.clone(), LevelValues::Scalar(vec![])))
+                } else {
+                    let data_mode = PARAMETER_DATA_MODE.get(i).cloned().unwrap_or(DATA_MODE.clone());
+                    if data_mode == "R" || param == "NB_SAMPLE_CTD" {
+                        Ok((param.clone(), LevelValues::Scalar(vec![])))
+                    } else {
+                        let adjusted_variable_name = format!("{}_ADJUSTED", param);
+                        Ok((param.clone(), unpack_param_levels(&adjusted_variable_name, pfl, N_LEVELS, &file)?))
+                    }
+                }
+            })
+            .collect::<Result<_, Error>>()
+            .map(Some)?;
+        if let Some(adjusted_data) = &mut adjusted_data {
+            adjusted_data.retain(|_, v| !v.is_empty());
+        }
+
+        // synthetic profiles record, for each parameter, how far each level's value was
+        // measured from the level's PRES; other files have no <PARAM>_dPRES
+        let mut dPRES: Option<HashMap<String, Vec<f64>>> = STATION_PARAMETERS.iter()
+            .map(|param| {
+                if param.is_empty() {
+                    Ok((param.clone(), vec![]))
+                } else {
+                    let dpres_variable_name = format!("{}_dPRES", param);
+                    Ok((param.clone(), unpack_levels(&dpres_variable_name, pfl, N_LEVELS, &file)?))
+                }
+            })
+            .collect::<Result<_, Error>>()
+            .map(Some)?;
+        if let Some(dpres) = &mut dPRES {
+            dpres.retain(|_, v| !v.is_empty());
+        }
+        let mut dPRES = dPRES.filter(|dpres| !dpres.is_empty());
+
+        let mut level_qc: Option<HashMap<String, Vec<String>>> = STATION_PARAMETERS.iter()
+            .map(|param| {
+                if param.is_empty() {
+                    Ok((param.clone(), vec![]))
+                } else {
+                    let qc_variable_name = format!("{}_QC", param);
+                    let mut qc_vec = unpack_string_array(&qc_variable_name, STRING1, N_LEVELS, [pfl..(pfl+1), 0..N_LEVELS].into(), &file);
+                    if let Some(pos) = qc_vec.iter().rposition(|x| x != "") {
+                        qc_vec.truncate(pos + 1);
+                    }
+                    Ok((param.clone(), qc_vec))
+                }
+            })
+            .collect::<Result<_, Error>>()
+            .map(Some)?;
+        if let Some(level_qc) = &mut level_qc {
+            level_qc.retain(|_, v| !v.is_empty() && !v.iter().all(|x| x == ""));
+        }
+            
+        let mut adjusted_level_qc: Option<HashMap<String, Vec<String>>> = STATION_PARAMETERS.iter()
+            .enumerate()
+            .map(|(i, param)| {
+                if param.is_empty() {
+                    Ok((param.clone(), vec![]))
+                } else {
+                    let data_mode = PARAMETER_DATA_MODE.get(i).cloned().unwrap_or(DATA_MODE.clone());
+                    if data_mode == "R" || param == "NB_SAMPLE_CTD" {
+                        Ok((param.clone(), vec![]))
+                    } else {
+                        let qc_variable_name = format!("{}_ADJUSTED_QC", param);
+                        let mut qc_vec = unpack_string_array(&qc_variable_name, STRING1, N_LEVELS, [pfl..(pfl+1), 0..N_LEVELS].into(), &file);
+                        if let Some(pos) = qc_vec.iter().rposition(|x| x != "") {
+                            qc_vec.truncate(pos + 1);
+                        }
+                        Ok((param.clone(), qc_vec))
+                    }
+                }
+            })
+            .collect::<Result<_, Error>>()
+            .map(Some)?;
+        if let Some(adjusted_level_qc) = &mut adjusted_level_qc {
+            adjusted_level_qc.retain(|_, v| !v.is_empty());
+        }
+
+        // make sure we didn't truncate too many fill values
+        // Find the maximum length among all vectors in the HashMaps
+        let max_len = realtime_data.as_ref().map_or(0, |m| m.values().map(|v| v.len()).max().unwrap_or(0))
+        .max(adjusted_data.as_ref().map_or(0, |m| m.values().map(|v| v.len()).max().unwrap_or(0)))
+        .max(level_qc.as_ref().map_or(0, |m| m.values().map(|v| v.len()).max().unwrap_or(0)))
+        .max(adjusted_level_qc.as_ref().map_or(0, |m| m.values().map(|v| v.len()).max().unwrap_or(0)))
+        .max(dPRES.as_ref().map_or(0, |m| m.values().map(|v| v.len()).max().unwrap_or(0)));
+        // Pad vectors in realtime_data and adjusted_data with 99999.0
+        if let Some(realtime_data) = &mut realtime_data {
+            for values in realtime_data.values_mut() {
+                values.resize(max_len);
+            }
+        }
+        if let Some(adjusted_data) = &mut adjusted_data {
+            for values in adjusted_data.values_mut() {
+                values.resize(max_len);
+            }
+        }
+        if let Some(dpres) = &mut dPRES {
+            for vec in dpres.values_mut() {
+                vec.resize(max_len, 99999.0);
+            }
+        }
+        // Pad vectors in level_qc and adjusted_level_qc with ""
+        if let Some(level_qc) = &mut level_qc {
+            for vec in level_qc.values_mut() {
+                vec.resize(max_len, "".to_string());
+            }
+        }
+        if let Some(adjusted_level_qc) = &mut adjusted_level_qc {
+            for vec in adjusted_level_qc.values_mut() {
+                vec.resize(max_len, "".to_string());
+            }
+        }
+
+        let mut data_info: Option<HashMap<String, DataInfo>> = STATION_PARAMETERS.iter()
+            .enumerate()
+            .map(|(i, param)| {
+                if param.is_empty() || param == "NB_SAMPLE_CTD" {
+                    Ok((param.clone(), DataInfo {
+                        DATA_MODE: "".to_string(),
+                        UNITS: "".to_string(),
+                        LONG_NAME: "".to_string(),
+                        PROFILE_PARAMETER_QC: "".to_string(),
+                        VALUES_DIMENSION: None,
+                        N_VALUES: None,
+                    }))
+                } else {
+                    // assumption: if PARAMETER_DATA_MODE exists, it should be used in lieu of DATA_MODE
+                    let data_mode = PARAMETER_DATA_MODE.get(i).cloned().unwrap_or(DATA_MODE.clone());
+                    match file.variable(param) {
+                        Some(variable) => {
+                            //let data_mode = PARAMETER_DATA_MODE.get(i).cloned().unwrap_or(DATA_MODE.clone());
+                            let units = string_attribute(&variable, "units")?;
+                            let long_name = string_attribute(&variable, "long_name")?;
+                            let qc_variable_name = format!("PROFILE_{}_QC", param);
+                            let qc_value = unpack_string(&qc_variable_name, STRING1, [pfl..(pfl+1)].into(), &file);
+                            let (values_dimension, n_values) = values_dimension(&variable).unzip();
+                            Ok((param.clone(), DataInfo {
+                                DATA_MODE: data_mode,
+                                UNITS: units,
+                                LONG_NAME: long_name,
+                                PROFILE_PARAMETER_QC: qc_value,
+                                VALUES_DIMENSION: values_dimension,
+                                N_VALUES: n_values,
+                            }))
+                        },
+                        None => Ok((param.clone(), DataInfo {
+                            DATA_MODE: "".to_string(),
+                            UNITS: "".to_string(),
+                            LONG_NAME: "".to_string(),
+                            PROFILE_PARAMETER_QC: "".to_string(),
+                            VALUES_DIMENSION: None,
+                            N_VALUES: None,
+                        })),
+                    }   
+                }
+            })
+            .collect::<Result<_, Error>>()
+            .map(Some)?;
+        if let Some(data_info) = &mut data_info {
+            data_info.remove("");
+        }
+
+        // let adjusted_level_error: HashMap<String, Vec<f64>> = STATION_PARAMETERS.iter()
+        //     .map(|param| {
+        //         let adjusted_variable_name = format!("{}_ADJUSTED_ERROR", param);
+        //         let variable = file.variable(&adjusted_variable_name).expect(&format!("Could not find variable '{}'", adjusted_variable_name));
+        //         let data: Vec<f64> = variable.get_values([..1, ..N_LEVELS])?;
+        //         Ok((param.clone(), data))
+        //     })
+        //     .collect::<Result<_, Box<dyn Error>>>()?;
+        
+        // construct the structs for this file ///////////////////////////////
+    
+        let data_object = DataSchema {
+            _id: format!("{}_{}", id, pfl),
+            geolocation: GeoJSONPoint {
+                location_type: "Point".to_string(),
+                coordinates: [LONGITUDE, LATITUDE],
+            },
+            CYCLE_NUMBER: CYCLE_NUMBER,
+            DIRECTION: DIRECTION,
+            DATA_STATE_INDICATOR: DATA_STATE_INDICATOR,
+            DATA_MODE: DATA_MODE,
+            DATE_CREATION: DATE_CREATION,
+            DATE_UPDATE: DATE_UPDATE,
+            DC_REFERENCE: DC_REFERENCE,
+            JULD: JULD,
+            JULD_QC: JULD_QC,
+            JULD_LOCATION: JULD_LOCATION,
+            POSITION_QC: POSITION_QC,
+            VERTICAL_SAMPLING_SCHEME: VERTICAL_SAMPLING_SCHEME,
+            sampling_scheme,
+            CONFIG_MISSION_NUMBER: CONFIG_MISSION_NUMBER,
+            STATION_PARAMETERS: STATION_PARAMETERS.clone(),
+            realtime_data: realtime_data,
+            adjusted_data: adjusted_data,
+            data_info: data_info,
+            level_qc: level_qc,
+            adjusted_level_qc: adjusted_level_qc,
+            dPRES,
+            DATA_TYPE: DATA_TYPE,
+            FORMAT_VERSION: FORMAT_VERSION,
+            HANDBOOK_VERSION: HANDBOOK_VERSION,
+            REFERENCE_DATE_TIME: REFERENCE_DATE_TIME,
+            PROJECT_NAME: PROJECT_NAME,
+            PI_NAME: split_string(PI_NAME, ','),
+            DATA_CENTRE: DATA_CENTRE,
+            PLATFORM_TYPE: PLATFORM_TYPE,
+            PLATFORM_NUMBER: PLATFORM_NUMBER,
+            FLOAT_SERIAL_NO: FLOAT_SERIAL_NO,
+            FIRMWARE_VERSION: FIRMWARE_VERSION,
+            WMO_INST_TYPE: WMO_INST_TYPE,
+            POSITIONING_SYSTEM: POSITIONING_SYSTEM,
+            source_file: source_file.to_string(),
+            core_source_file: None,
+            source_file_sha256: version.sha256.clone(),
+            source_file_size: version.size,
+            source_file_mtime: version.mtime.clone(),
+            first_ingested: now,
+            last_modified: now,
+        };
+
+        let map_object = MapSchema {
+            _id: format!("{}_{}", id, pfl),
+            geolocation: GeoJSONPoint {
+                location_type: "Point".to_string(),
+                coordinates: [LONGITUDE, LATITUDE],
+            },
+            JULD: JULD,
+            STATION_PARAMETERS: STATION_PARAMETERS,
+            sampling_scheme,
+            source_file: source_file.to_string(),
+            first_ingested: now,
+            last_modified: now,
+        };
+
+        //println!("{:?}", data_object);
+
+        profiles.push(ProfileDoc { data: data_object, search: map_object });
+    }
+    
+    Ok(profiles)
+}
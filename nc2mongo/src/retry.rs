@@ -0,0 +1,81 @@
+// retry MongoDB operations through network blips and primary elections
+use std::future::Future;
+use std::time::Duration;
+use mongodb::ClientSession;
+use mongodb::error::{ErrorKind, RETRYABLE_WRITE_ERROR, TRANSIENT_TRANSACTION_ERROR, UNKNOWN_TRANSACTION_COMMIT_RESULT};
+use crate::error::Error;
+
+// server error codes that mean "try again shortly": network trouble, elections, shutdowns
+const RETRYABLE_CODES: [i32; 12] = [6, 7, 89, 91, 189, 262, 9001, 10107, 11600, 11602, 13435, 13436];
+
+#[derive(Clone, Copy, Debug)]
+pub struct RetryPolicy {
+    // total tries, including the first
+    pub attempts: u32,
+    // wait before the first retry; doubles for each one after, up to max_delay
+    pub initial_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl RetryPolicy {
+    // run op until it succeeds, fails permanently, or runs out of attempts
+    pub async fn run<T, F, Fut>(&self, what: &str, mut op: F) -> Result<T, Error>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, Error>>,
+    {
+        let mut delay = self.initial_delay;
+        let mut attempt = 1;
+        loop {
+            match op().await {
+                Err(e) if attempt < self.attempts && is_retryable(&e) => {
+                    eprintln!("{} failed (attempt {} of {}), retrying in {:?}: {}", what, attempt, self.attempts, delay, e);
+                    tokio::time::sleep(delay).await;
+                    delay = (delay * 2).min(self.max_delay);
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    // commit session's transaction, retrying only the commit while its outcome is unknown.
+    // The commit may have gone through, so running the whole transaction again could find
+    // its own writes already in place.
+    pub async fn commit(&self, what: &str, session: &mut ClientSession) -> Result<(), Error> {
+        let mut delay = self.initial_delay;
+        let mut attempt = 1;
+        loop {
+            match session.commit_transaction().await {
+                Err(e) if attempt < self.attempts && e.contains_label(UNKNOWN_TRANSACTION_COMMIT_RESULT) => {
+                    eprintln!("{} failed to commit (attempt {} of {}), retrying the commit in {:?}: {}", what, attempt, self.attempts, delay, e);
+                    tokio::time::sleep(delay).await;
+                    delay = (delay * 2).min(self.max_delay);
+                    attempt += 1;
+                }
+                result => return Ok(result?),
+            }
+        }
+    }
+}
+
+pub fn is_retryable(e: &Error) -> bool {
+    let e = match e {
+        Error::Mongo(e) => e,
+        _ => return false,
+    };
+    // a commit whose outcome is unknown is retried on its own, by commit, never as a whole transaction
+    if e.contains_label(UNKNOWN_TRANSACTION_COMMIT_RESULT) {
+        return false;
+    }
+    if [RETRYABLE_WRITE_ERROR, TRANSIENT_TRANSACTION_ERROR].iter().any(|label| e.contains_label(label)) {
+        return true;
+    }
+    match &*e.kind {
+        ErrorKind::Io(_) | ErrorKind::ConnectionPoolCleared { .. } | ErrorKind::ServerSelection { .. } => true,
+        ErrorKind::Command(err) => RETRYABLE_CODES.contains(&err.code),
+        ErrorKind::Write(mongodb::error::WriteFailure::WriteConcernError(err)) => RETRYABLE_CODES.contains(&err.code),
+        ErrorKind::BulkWrite(failure) => matches!(&failure.write_concern_error, Some(err) if RETRYABLE_CODES.contains(&err.code)),
+        _ => false,
+    }
+}
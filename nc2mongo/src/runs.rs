@@ -0,0 +1,88 @@
+// what one batch run did, saved to the runs collection so nightly syncs can be audited
+use std::time::Instant;
+use serde::Serialize;
+
+// a run document embeds no more failures than this, so a run where every file fails still
+// fits in MongoDB's 16 MB document limit; failure_count has the full number
+const MAX_RECORDED_FAILURES: usize = 1000;
+
+#[derive(Debug, Serialize)]
+pub struct FileFailure {
+    pub file: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RunSummary {
+    // ingest, rebuild, merge, update or retry-failures
+    pub command: String,
+    // the rsync log directory an update processed
+    pub log_dir: Option<String>,
+    pub started: bson::DateTime,
+    pub finished: Option<bson::DateTime>,
+    pub duration_seconds: f64,
+    // files handed to ingest
+    pub files: usize,
+    // entries passed over before ingest: walk filters, journaled files and, for a merge, files that are not B-files
+    pub skipped: usize,
+    pub profiles_written: usize,
+    pub unchanged: usize,
+    pub stale: usize,
+    pub deleted: usize,
+    pub failure_count: usize,
+    // the first MAX_RECORDED_FAILURES of them
+    pub failures: Vec<FileFailure>,
+    #[serde(skip)]
+    clock: Option<Instant>,
+}
+
+impl RunSummary {
+    pub fn start(command: &str) -> Self {
+        RunSummary {
+            command: command.to_string(),
+            log_dir: None,
+            started: bson::DateTime::now(),
+            finished: None,
+            duration_seconds: 0.0,
+            files: 0,
+            skipped: 0,
+            profiles_written: 0,
+            unchanged: 0,
+            stale: 0,
+            deleted: 0,
+            failure_count: 0,
+            failures: Vec::new(),
+            clock: Some(Instant::now()),
+        }
+    }
+
+    pub fn fail(&mut self, file: &str, reason: String) {
+        self.failure_count += 1;
+        if self.failures.len() < MAX_RECORDED_FAILURES {
+            self.failures.push(FileFailure { file: file.to_string(), reason });
+        }
+    }
+
+    pub fn finish(&mut self) {
+        self.finished = Some(bson::DateTime::now());
+        if let Some(clock) = self.clock {
+            self.duration_seconds = clock.elapsed().as_secs_f64();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn failures_beyond_the_limit_are_only_counted() {
+        let mut summary = RunSummary::start("rebuild");
+        for n in 0..MAX_RECORDED_FAILURES + 5 {
+            summary.fail(&format!("R1901727_{:03}.nc", n), "is not under a ifremer/ mirror directory".to_string());
+        }
+        assert_eq!(summary.failure_count, MAX_RECORDED_FAILURES + 5);
+        assert_eq!(summary.failures.len(), MAX_RECORDED_FAILURES);
+        assert_eq!(summary.failures[0].file, "R1901727_000.nc");
+    }
+}
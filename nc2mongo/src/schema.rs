@@ -0,0 +1,156 @@
+// the documents stored for each profile
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GeoJSONPoint {
+    #[serde(rename = "type")]
+    pub location_type: String,
+    pub coordinates: [f64; 2],
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DataInfo {
+    pub DATA_MODE: String,
+    pub UNITS: String,
+    pub LONG_NAME: String,
+    pub PROFILE_PARAMETER_QC: String,
+    // for a parameter with an array of values at each level, the N_VALUES<k> dimension
+    // of those arrays and its length
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub VALUES_DIMENSION: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub N_VALUES: Option<usize>,
+}
+
+// a parameter's values down the profile: one per level, or for a parameter with an
+// N_VALUES dimension, such as UV_INTENSITY_NITRATE, an array per level
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum LevelValues {
+    Scalar(Vec<f64>),
+    Array(Vec<Vec<f64>>),
+}
+
+impl LevelValues {
+    // number of levels
+    pub fn len(&self) -> usize {
+        match self {
+            LevelValues::Scalar(values) => values.len(),
+            LevelValues::Array(values) => values.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // pad with fill values, or cut, to the given number of levels
+    pub fn resize(&mut self, levels: usize) {
+        match self {
+            LevelValues::Scalar(values) => values.resize(levels, 99999.0),
+            LevelValues::Array(values) => {
+                let width = values.first().map_or(0, |level| level.len());
+                values.resize(levels, vec![99999.0; width]);
+            }
+        }
+    }
+}
+
+// which of a cycle's profiles this is, from the part of VERTICAL_SAMPLING_SCHEME before
+// the colon (Argo reference table 16). Files with N_PROF > 1 hold one primary profile
+// plus auxiliary ones.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SamplingScheme {
+    Primary,
+    Secondary,
+    NearSurface,
+    Bounce,
+    // blank, or not a scheme in the reference table
+    Unknown,
+}
+
+impl SamplingScheme {
+    pub fn parse(vertical_sampling_scheme: &str) -> SamplingScheme {
+        let name = vertical_sampling_scheme.split(':').next().unwrap_or_default().trim();
+        match name.to_ascii_lowercase().as_str() {
+            "primary sampling" => SamplingScheme::Primary,
+            "secondary sampling" => SamplingScheme::Secondary,
+            "near-surface sampling" => SamplingScheme::NearSurface,
+            "bounce sampling" => SamplingScheme::Bounce,
+            _ => SamplingScheme::Unknown,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DataSchema {
+    pub _id: String,
+    pub geolocation: GeoJSONPoint,
+    pub CYCLE_NUMBER: i32,
+    pub DIRECTION: String,
+    pub DATA_STATE_INDICATOR: String,
+    pub DATA_MODE: String,
+    pub DATE_CREATION: String,
+    pub DATE_UPDATE: String,
+    pub DC_REFERENCE: String,
+    pub JULD: f64,
+    pub JULD_QC: String,
+    pub JULD_LOCATION: f64,
+    pub POSITION_QC: String,
+    pub VERTICAL_SAMPLING_SCHEME: String,
+    pub sampling_scheme: SamplingScheme,
+    pub CONFIG_MISSION_NUMBER: i32,
+    pub STATION_PARAMETERS: Vec<String>,
+    pub realtime_data: Option<HashMap<String, LevelValues>>,
+    pub adjusted_data: Option<HashMap<String, LevelValues>>,
+    pub data_info: Option<HashMap<String, DataInfo>>,
+    pub level_qc: Option<HashMap<String, Vec<String>>>,
+    pub adjusted_level_qc: Option<HashMap<String, Vec<String>>>,
+    // <PARAM>_dPRES by parameter; only synthetic (S-file) profiles have it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dPRES: Option<HashMap<String, Vec<f64>>>,
+    pub DATA_TYPE: String,
+    pub FORMAT_VERSION: String,
+    pub HANDBOOK_VERSION: String,
+    pub REFERENCE_DATE_TIME: String,
+    pub PROJECT_NAME: String,
+    pub PI_NAME: Vec<String>,
+    pub DATA_CENTRE: String,
+    pub PLATFORM_TYPE: String,
+    pub PLATFORM_NUMBER: String,
+    pub FLOAT_SERIAL_NO: String,
+    pub FIRMWARE_VERSION: String,
+    pub WMO_INST_TYPE: String,
+    pub POSITIONING_SYSTEM: String,
+    pub source_file: String,
+    // for a merged profile, the core file joined with the B-file in source_file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub core_source_file: Option<String>,
+    pub source_file_sha256: String,
+    pub source_file_size: u64,
+    pub source_file_mtime: String,
+    pub first_ingested: bson::DateTime,
+    pub last_modified: bson::DateTime,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MapSchema {
+    pub _id: String,
+    pub geolocation: GeoJSONPoint,
+    pub JULD: f64,
+    pub STATION_PARAMETERS: Vec<String>,
+    pub sampling_scheme: SamplingScheme,
+    pub source_file: String,
+    pub first_ingested: bson::DateTime,
+    pub last_modified: bson::DateTime,
+}
+
+// everything stored for one profile: its full document for the argo collection and
+// its summary for argo_search
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ProfileDoc {
+    pub data: DataSchema,
+    pub search: MapSchema,
+}
@@ -0,0 +1,75 @@
+// keeps documents in memory, for exercising parsing without a database
+use std::collections::BTreeMap;
+use crate::error::Error;
+use std::sync::Mutex;
+use async_trait::async_trait;
+use super::{latest_date_update, Sink, SinkReport, WriteOutcome};
+use crate::schema::ProfileDoc;
+
+#[derive(Debug, Default)]
+pub struct MemorySink {
+    files: Mutex<BTreeMap<String, Vec<ProfileDoc>>>,
+    deleted: Mutex<Vec<String>>,
+    reprocess: bool,
+}
+
+impl MemorySink {
+    pub fn new() -> Self {
+        MemorySink::default()
+    }
+
+    // like MongoSink::reprocessing, take every file whatever is stored for it
+    pub fn reprocessing(self) -> Self {
+        MemorySink { reprocess: true, ..self }
+    }
+
+    // the profiles currently held for each source file
+    pub fn files(&self) -> BTreeMap<String, Vec<ProfileDoc>> {
+        self.files.lock().unwrap().clone()
+    }
+
+    // every source file deleted so far, in the order they were deleted
+    pub fn deleted(&self) -> Vec<String> {
+        self.deleted.lock().unwrap().clone()
+    }
+}
+
+#[async_trait]
+impl Sink for MemorySink {
+    async fn write_file(&self, source_file: &str, profiles: Vec<ProfileDoc>) -> Result<WriteOutcome, Error> {
+        let mut files = self.files.lock().unwrap();
+        // as in the database, no profiles at all means the file's documents just go
+        let stored = files.get(source_file).and_then(|stored| latest_date_update(stored));
+        if let (Some(incoming), Some(stored)) = (latest_date_update(&profiles), stored) {
+            if incoming <= stored && !self.reprocess {
+                return Ok(WriteOutcome::Stale);
+            }
+        }
+        if profiles.is_empty() {
+            files.remove(source_file);
+        } else {
+            files.insert(source_file.to_string(), profiles);
+        }
+        Ok(WriteOutcome::Written)
+    }
+
+    async fn is_unchanged(&self, source_file: &str, sha256: &str) -> Result<bool, Error> {
+        if self.reprocess {
+            return Ok(false);
+        }
+        let files = self.files.lock().unwrap();
+        let stored = files.get(source_file).and_then(|profiles| profiles.first());
+        Ok(matches!(stored, Some(profile) if profile.data.source_file_sha256 == sha256))
+    }
+
+    async fn delete_file(&self, source_file: &str) -> Result<(), Error> {
+        self.files.lock().unwrap().remove(source_file);
+        self.deleted.lock().unwrap().push(source_file.to_string());
+        Ok(())
+    }
+
+    async fn finish(&self) -> Result<SinkReport, Error> {
+        let profiles_written = self.files.lock().unwrap().values().map(|profiles| profiles.len()).sum();
+        Ok(SinkReport { profiles_written, ..Default::default() })
+    }
+}
@@ -0,0 +1,57 @@
+// where parsed documents get written
+use crate::error::Error;
+use async_trait::async_trait;
+use crate::bulk::RejectedDocument;
+use crate::schema::ProfileDoc;
+
+mod memory;
+mod mongo;
+mod ndjson;
+mod split;
+
+pub use memory::MemorySink;
+pub use mongo::{check_transactions, MongoSink};
+pub use ndjson::NdjsonSink;
+pub use split::SplitSink;
+
+// what a sink did with one file's profiles
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WriteOutcome {
+    Written,
+    // what is already stored has the same or a later DATE_UPDATE, so it was kept
+    Stale,
+}
+
+// the newest DATE_UPDATE among a file's profiles; DATE_UPDATE is YYYYMMDDHHMISS,
+// so string order is time order
+pub fn latest_date_update(profiles: &[ProfileDoc]) -> Option<&str> {
+    profiles.iter().map(|profile| profile.data.DATE_UPDATE.as_str()).max()
+}
+
+// totals a sink reports once a run is over
+#[derive(Debug, Default)]
+pub struct SinkReport {
+    pub profiles_written: usize,
+    pub duplicates: Vec<RejectedDocument>,
+    pub rejected: Vec<RejectedDocument>,
+}
+
+// a destination for the documents parsed out of netCDF files. Files are written
+// concurrently, so implementations take &self and handle their own locking.
+#[async_trait]
+pub trait Sink: Send + Sync {
+    // replace whatever source_file contributed before with profiles, unless what is
+    // stored is at least as new
+    async fn write_file(&self, source_file: &str, profiles: Vec<ProfileDoc>) -> Result<WriteOutcome, Error>;
+
+    // whether the documents already stored for source_file came from contents with this hash
+    async fn is_unchanged(&self, _source_file: &str, _sha256: &str) -> Result<bool, Error> {
+        Ok(false)
+    }
+
+    // source_file was deleted upstream, so drop whatever it contributed
+    async fn delete_file(&self, source_file: &str) -> Result<(), Error>;
+
+    // write out anything still buffered, once every file has been handed over
+    async fn finish(&self) -> Result<SinkReport, Error>;
+}
@@ -0,0 +1,226 @@
+// the argo and argo_search collections
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use async_trait::async_trait;
+use mongodb::{ClientSession, Collection, Database};
+use mongodb::bson::{doc, Bson, DateTime, Document};
+use mongodb::options::{FindOneOptions, UpdateOptions};
+use tokio::sync::Mutex;
+use super::{latest_date_update, Sink, SinkReport, WriteOutcome};
+use crate::bulk::BulkWriter;
+use crate::error::Error;
+use crate::journal::SharedJournal;
+use crate::retry::RetryPolicy;
+use crate::schema::{DataSchema, MapSchema, ProfileDoc};
+
+enum Mode {
+    // replace each file's old documents with its new ones, one transaction per file,
+    // archiving the old argo documents in the history collection
+    Upsert(Collection<Document>),
+    // collections start out empty: batch inserts, journaling files as they commit
+    Rebuild(Box<Mutex<(BulkWriter, Option<SharedJournal>)>>),
+}
+
+pub struct MongoSink {
+    argo: Collection<DataSchema>,
+    argo_search: Collection<MapSchema>,
+    mode: Mode,
+    retry: RetryPolicy,
+    // rewrite every file whatever is stored for it, e.g. to apply a parser fix
+    reprocess: bool,
+    profiles_written: AtomicUsize,
+}
+
+impl MongoSink {
+    pub fn upsert(argo: Collection<DataSchema>, argo_search: Collection<MapSchema>, argo_history: Collection<Document>, retry: RetryPolicy) -> Self {
+        MongoSink { argo, argo_search, mode: Mode::Upsert(argo_history), retry, reprocess: false, profiles_written: AtomicUsize::new(0) }
+    }
+
+    pub fn rebuild(argo: Collection<DataSchema>, argo_search: Collection<MapSchema>, batch_size: usize, journal: Option<SharedJournal>, retry: RetryPolicy) -> Self {
+        let bulk = BulkWriter::new(argo.clone(), argo_search.clone(), batch_size, retry);
+        let mode = Mode::Rebuild(Box::new(Mutex::new((bulk, journal))));
+        MongoSink { argo, argo_search, mode, retry, reprocess: false, profiles_written: AtomicUsize::new(0) }
+    }
+
+    // skip both the unchanged-hash and the DATE_UPDATE checks, so documents stored by an
+    // older parser are replaced even though their files have not changed
+    pub fn reprocessing(self, reprocess: bool) -> Self {
+        MongoSink { reprocess, ..self }
+    }
+}
+
+// upserts replace each file in a transaction, which a standalone mongod cannot run; find
+// that out before the first file rather than failing every one of them
+pub async fn check_transactions(db: &Database) -> Result<(), Error> {
+    let hello = db.run_command(doc! { "isMaster": 1 }, None).await?;
+    let replica_set = hello.contains_key("setName");
+    let mongos = hello.get_str("msg") == Ok("isdbgrid");
+    if replica_set || mongos {
+        Ok(())
+    } else {
+        Err(Error::Setup("MongoDB is a standalone server, but updates need transactions; run it as a replica set, or use --rebuild into empty collections".to_string()))
+    }
+}
+
+impl MongoSink {
+    // swap out everything source_file contributed for profiles in one transaction, so a
+    // failure partway through leaves the previous documents in place. A transaction that
+    // fails for a transient reason is retried from the start.
+    async fn replace_file(&self, source_file: &str, profiles: Vec<ProfileDoc>) -> Result<WriteOutcome, Error> {
+        let what = format!("Writing {}", source_file);
+        self.retry.run(&what, || self.try_replace_file(&what, source_file, profiles.clone())).await
+    }
+
+    async fn try_replace_file(&self, what: &str, source_file: &str, profiles: Vec<ProfileDoc>) -> Result<WriteOutcome, Error> {
+        let mut session = self.argo.client().start_session(None).await?;
+        session.start_transaction(None).await?;
+        let outcome = match self.replace_in_session(&mut session, source_file, profiles).await {
+            Ok(outcome) => outcome,
+            Err(e) => {
+                // the write's own error is the one to report and to judge retries by
+                if let Err(abort) = session.abort_transaction().await {
+                    eprintln!("Could not abort transaction for {}: {}", source_file, abort);
+                }
+                return Err(e);
+            }
+        };
+        self.retry.commit(what, &mut session).await?;
+        Ok(outcome)
+    }
+
+    async fn replace_in_session(&self, session: &mut ClientSession, source_file: &str, mut profiles: Vec<ProfileDoc>) -> Result<WriteOutcome, Error> {
+        // replaying an old update list must not roll the database back; deletions always apply
+        if let Some(incoming) = latest_date_update(&profiles).filter(|_| !self.reprocess) {
+            let options = FindOneOptions::builder()
+                .sort(doc! { "DATE_UPDATE": -1 })
+                .projection(doc! { "DATE_UPDATE": 1 })
+                .build();
+            let stored = self.argo.clone_with_type::<Document>()
+                .find_one_with_session(doc! { "source_file": source_file }, options, &mut *session).await?;
+            if let Some(stored) = stored.as_ref().and_then(|d| d.get_str("DATE_UPDATE").ok()) {
+                if incoming <= stored {
+                    return Ok(WriteOutcome::Stale);
+                }
+            }
+        }
+
+        // archive the versions about to be replaced, remembering when each profile first arrived
+        let now = DateTime::now();
+        let mut first_ingested = HashMap::new();
+        let mut previous = Vec::new();
+        let mut cursor = self.argo.clone_with_type::<Document>()
+            .find_with_session(doc! { "source_file": source_file }, None, &mut *session).await?;
+        while let Some(mut old) = cursor.next(&mut *session).await.transpose()? {
+            let id = old.remove("_id").unwrap_or(Bson::Null);
+            if let (Bson::String(id), Ok(ingested)) = (&id, old.get_datetime("first_ingested")) {
+                first_ingested.insert(id.clone(), *ingested);
+            }
+            old.insert("profile_id", id);
+            old.insert("superseded", now);
+            previous.push(old);
+        }
+        if let Mode::Upsert(argo_history) = &self.mode {
+            if !previous.is_empty() {
+                argo_history.insert_many_with_session(previous, None, &mut *session).await?;
+            }
+        }
+        for ProfileDoc { data: data_object, search: map_object } in profiles.iter_mut() {
+            if let Some(ingested) = first_ingested.get(&data_object._id) {
+                data_object.first_ingested = *ingested;
+                map_object.first_ingested = *ingested;
+            }
+            data_object.last_modified = now;
+            map_object.last_modified = now;
+        }
+
+        self.argo.delete_many_with_session(doc! { "source_file": source_file }, None, &mut *session).await?;
+        self.argo_search.delete_many_with_session(doc! { "source_file": source_file }, None, &mut *session).await?;
+
+        for ProfileDoc { data: data_object, search: map_object } in profiles {
+            let filter = doc! {
+                "_id": data_object._id.clone(),
+            };
+            let update = doc! {
+                "$set": bson::to_bson(&data_object)?,
+            };
+            let options = UpdateOptions::builder().upsert(true).build();
+            self.argo.update_one_with_session(filter, update, options, &mut *session).await?;
+
+            let map_filter = doc! {
+                "_id": map_object._id.clone(),
+            };
+            let map_update = doc! {
+                "$set": bson::to_bson(&map_object)?,
+            };
+            let map_options = UpdateOptions::builder().upsert(true).build();
+            self.argo_search.update_one_with_session(map_filter, map_update, map_options, &mut *session).await?;
+        }
+        Ok(WriteOutcome::Written)
+    }
+}
+
+#[async_trait]
+impl Sink for MongoSink {
+    async fn write_file(&self, source_file: &str, profiles: Vec<ProfileDoc>) -> Result<WriteOutcome, Error> {
+        match &self.mode {
+            Mode::Upsert(_) => {
+                let nprofiles = profiles.len();
+                let outcome = self.replace_file(source_file, profiles).await?;
+                if outcome == WriteOutcome::Written {
+                    self.profiles_written.fetch_add(nprofiles, Ordering::Relaxed);
+                }
+                Ok(outcome)
+            }
+            Mode::Rebuild(state) => {
+                let mut state = state.lock().await;
+                let (bulk, journal) = &mut *state;
+                let committed = bulk.push(source_file.to_string(), profiles).await?;
+                if let Some(journal) = journal.as_ref() {
+                    journal.lock().await.record(&committed).await?;
+                }
+                Ok(WriteOutcome::Written)
+            }
+        }
+    }
+
+    async fn is_unchanged(&self, source_file: &str, sha256: &str) -> Result<bool, Error> {
+        // a rebuild starts from empty collections, so there is nothing to compare against
+        if let Mode::Rebuild(_) = self.mode {
+            return Ok(false);
+        }
+        if self.reprocess {
+            return Ok(false);
+        }
+        let filter = doc! { "source_file": source_file, "source_file_sha256": sha256 };
+        let what = format!("Checking {}", source_file);
+        let count = self.retry.run(&what, || async { Ok(self.argo.count_documents(filter.clone(), None).await?) }).await?;
+        Ok(count > 0)
+    }
+
+    async fn delete_file(&self, source_file: &str) -> Result<(), Error> {
+        // nothing to delete from a rebuild's empty collections
+        if let Mode::Upsert(_) = self.mode {
+            self.replace_file(source_file, Vec::new()).await?;
+        }
+        Ok(())
+    }
+
+    async fn finish(&self) -> Result<SinkReport, Error> {
+        match &self.mode {
+            Mode::Upsert(_) => Ok(SinkReport { profiles_written: self.profiles_written.load(Ordering::Relaxed), ..Default::default() }),
+            Mode::Rebuild(state) => {
+                let mut state = state.lock().await;
+                let (bulk, journal) = &mut *state;
+                let committed = bulk.flush().await?;
+                if let Some(journal) = journal.as_ref() {
+                    journal.lock().await.record(&committed).await?;
+                }
+                Ok(SinkReport {
+                    profiles_written: bulk.inserted,
+                    duplicates: std::mem::take(&mut bulk.duplicates),
+                    rejected: std::mem::take(&mut bulk.rejected),
+                })
+            }
+        }
+    }
+}
@@ -0,0 +1,89 @@
+// newline-delimited JSON output, one line per document that would be written to the database
+use crate::error::Error;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use async_trait::async_trait;
+use serde::Serialize;
+use super::{Sink, SinkReport, WriteOutcome};
+use crate::config::Collections;
+use crate::schema::ProfileDoc;
+
+#[derive(Serialize)]
+struct DocumentLine<'a, T> {
+    collection: &'a str,
+    document: &'a T,
+}
+
+#[derive(Serialize)]
+struct DeletionLine<'a> {
+    collection: &'a str,
+    delete_source_file: &'a str,
+}
+
+pub struct NdjsonSink {
+    out: Arc<Mutex<Box<dyn Write + Send>>>,
+    // collection names written on each line
+    argo: String,
+    argo_search: String,
+    profiles_written: Mutex<usize>,
+}
+
+impl NdjsonSink {
+    // write to path, or to stdout if there is none
+    pub fn create(path: Option<&Path>, collections: &Collections) -> io::Result<Self> {
+        let out: Box<dyn Write + Send> = match path {
+            Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+            None => Box::new(BufWriter::new(io::stdout())),
+        };
+        Ok(NdjsonSink {
+            out: Arc::new(Mutex::new(out)),
+            argo: collections.argo.clone(),
+            argo_search: collections.argo_search.clone(),
+            profiles_written: Mutex::new(0),
+        })
+    }
+
+    // another sink writing to the same output, under other collection names
+    pub fn with_collections(&self, argo: &str, argo_search: &str) -> Self {
+        NdjsonSink {
+            out: self.out.clone(),
+            argo: argo.to_string(),
+            argo_search: argo_search.to_string(),
+            profiles_written: Mutex::new(0),
+        }
+    }
+}
+
+fn write_line<T: Serialize>(out: &mut dyn Write, line: &T) -> io::Result<()> {
+    serde_json::to_writer(&mut *out, line)?;
+    out.write_all(b"\n")
+}
+
+#[async_trait]
+impl Sink for NdjsonSink {
+    async fn write_file(&self, _source_file: &str, profiles: Vec<ProfileDoc>) -> Result<WriteOutcome, Error> {
+        // hold the lock for the whole file so its lines stay together
+        let mut out = self.out.lock().unwrap();
+        for profile in &profiles {
+            write_line(out.as_mut(), &DocumentLine { collection: &self.argo, document: &profile.data })?;
+            write_line(out.as_mut(), &DocumentLine { collection: &self.argo_search, document: &profile.search })?;
+        }
+        *self.profiles_written.lock().unwrap() += profiles.len();
+        Ok(WriteOutcome::Written)
+    }
+
+    async fn delete_file(&self, source_file: &str) -> Result<(), Error> {
+        let mut out = self.out.lock().unwrap();
+        for collection in [&self.argo, &self.argo_search] {
+            write_line(out.as_mut(), &DeletionLine { collection, delete_source_file: source_file })?;
+        }
+        Ok(())
+    }
+
+    async fn finish(&self) -> Result<SinkReport, Error> {
+        self.out.lock().unwrap().flush()?;
+        Ok(SinkReport { profiles_written: *self.profiles_written.lock().unwrap(), ..Default::default() })
+    }
+}
@@ -0,0 +1,50 @@
+// send synthetic profile files to one sink and everything else to another
+use crate::error::Error;
+use async_trait::async_trait;
+use super::{Sink, SinkReport, WriteOutcome};
+use crate::schema::ProfileDoc;
+use crate::walk;
+
+pub struct SplitSink {
+    core: Box<dyn Sink>,
+    synthetic: Box<dyn Sink>,
+}
+
+impl SplitSink {
+    pub fn new(core: impl Sink + 'static, synthetic: impl Sink + 'static) -> Self {
+        SplitSink { core: Box::new(core), synthetic: Box::new(synthetic) }
+    }
+
+    // S-files are told apart by the prefix of the file name at the end of source_file
+    fn route(&self, source_file: &str) -> &dyn Sink {
+        let name = source_file.rsplit('/').next().unwrap_or_default();
+        match walk::parse_profile_name(name) {
+            Some((prefix, _)) if prefix.is_synthetic() => self.synthetic.as_ref(),
+            _ => self.core.as_ref(),
+        }
+    }
+}
+
+#[async_trait]
+impl Sink for SplitSink {
+    async fn write_file(&self, source_file: &str, profiles: Vec<ProfileDoc>) -> Result<WriteOutcome, Error> {
+        self.route(source_file).write_file(source_file, profiles).await
+    }
+
+    async fn is_unchanged(&self, source_file: &str, sha256: &str) -> Result<bool, Error> {
+        self.route(source_file).is_unchanged(source_file, sha256).await
+    }
+
+    async fn delete_file(&self, source_file: &str) -> Result<(), Error> {
+        self.route(source_file).delete_file(source_file).await
+    }
+
+    async fn finish(&self) -> Result<SinkReport, Error> {
+        let mut report = self.core.finish().await?;
+        let synthetic = self.synthetic.finish().await?;
+        report.profiles_written += synthetic.profiles_written;
+        report.duplicates.extend(synthetic.duplicates);
+        report.rejected.extend(synthetic.rejected);
+        Ok(report)
+    }
+}
@@ -0,0 +1,122 @@
+// find and vet the rsync log directory a nightly update should process
+use crate::error::Error;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+// names of the log directories whose updates have been processed, one per line. It lives
+// in the log root rather than in each directory, since writing into a directory would
+// make it the most recently modified one.
+const PROCESSED_LIST: &str = "nc2mongo.processed";
+
+// the most recently modified subdirectory of root, if there is one
+pub fn latest_log_dir(root: &Path) -> io::Result<Option<PathBuf>> {
+    let mut latest: Option<(SystemTime, PathBuf)> = None;
+    for entry in fs::read_dir(root)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        if !metadata.is_dir() {
+            continue;
+        }
+        let modified = metadata.modified()?;
+        let newer = match &latest {
+            Some((newest, _)) => modified > *newest,
+            None => true,
+        };
+        if newer {
+            latest = Some((modified, entry.path()));
+        }
+    }
+    Ok(latest.map(|(_, path)| path))
+}
+
+// refuse directories that were already processed, or that are too old to be last night's sync
+pub fn check_log_dir(dir: &Path, max_age: Duration) -> Result<(), Error> {
+    if is_processed(dir)? {
+        return Err(Error::Setup(format!("{} was already processed; did last night's sync run?", dir.display())));
+    }
+    let modified = fs::metadata(dir)?.modified()?;
+    let age = SystemTime::now().duration_since(modified).unwrap_or_default();
+    if age > max_age {
+        return Err(Error::Setup(format!("{} is {} hours old; did last night's sync run?", dir.display(), age.as_secs() / 3600)));
+    }
+    Ok(())
+}
+
+fn is_processed(dir: &Path) -> io::Result<bool> {
+    let (root, name) = split_log_dir(dir);
+    match fs::read_to_string(root.join(PROCESSED_LIST)) {
+        Ok(list) => Ok(list.lines().any(|line| line == name)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+pub fn mark_processed(dir: &Path) -> io::Result<()> {
+    let (root, name) = split_log_dir(dir);
+    let mut list = fs::OpenOptions::new().create(true).append(true).open(root.join(PROCESSED_LIST))?;
+    writeln!(list, "{}", name)
+}
+
+fn split_log_dir(dir: &Path) -> (&Path, String) {
+    let root = dir.parent().unwrap_or_else(|| Path::new("."));
+    let name = dir.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+    (root, name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use tempfile::TempDir;
+
+    const DAY: Duration = Duration::from_secs(24 * 3600);
+
+
+    fn log_dir(root: &Path, name: &str, age: Duration) -> PathBuf {
+        let dir = root.join(name);
+        fs::create_dir(&dir).unwrap();
+        File::open(&dir).unwrap().set_modified(SystemTime::now() - age).unwrap();
+        dir
+    }
+
+    #[test]
+    fn a_recent_unprocessed_directory_passes() {
+        let root = TempDir::new().unwrap();
+        let dir = log_dir(root.path(), "2024-03-02", Duration::from_secs(3600));
+        let checked = check_log_dir(&dir, DAY * 3 / 2);
+        assert!(checked.is_ok());
+    }
+
+    #[test]
+    fn an_old_directory_is_refused() {
+        let root = TempDir::new().unwrap();
+        let dir = log_dir(root.path(), "2024-03-02", DAY * 2);
+        let checked = check_log_dir(&dir, DAY * 3 / 2);
+        assert!(matches!(checked, Err(Error::Setup(message)) if message.contains("48 hours old")));
+    }
+
+    #[test]
+    fn a_processed_directory_is_refused() {
+        let root = TempDir::new().unwrap();
+        let dir = log_dir(root.path(), "2024-03-02", Duration::from_secs(3600));
+        mark_processed(&dir).unwrap();
+        let checked = check_log_dir(&dir, DAY * 3 / 2);
+        assert!(matches!(checked, Err(Error::Setup(message)) if message.contains("already processed")));
+    }
+
+    // a run that finishes after the next night's directory appears must not make its own
+    // directory look newest
+    #[test]
+    fn marking_a_directory_processed_leaves_the_newest_directory_newest() {
+        let root = TempDir::new().unwrap();
+        let processed = log_dir(root.path(), "2024-03-01", DAY);
+        let newest = log_dir(root.path(), "2024-03-02", Duration::from_secs(3600));
+        mark_processed(&processed).unwrap();
+        let latest = latest_log_dir(root.path()).unwrap();
+        let checked = check_log_dir(&newest, DAY * 3 / 2);
+        assert_eq!(latest, Some(newest));
+        assert!(checked.is_ok());
+    }
+}
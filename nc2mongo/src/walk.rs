@@ -0,0 +1,251 @@
+// walk a GDAC mirror laid out as <root>/<dac>/<wmo>/profiles/<prefix><wmo>_<cycle>.nc
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use clap::ValueEnum;
+
+// profile file name prefixes, in the GDAC naming convention
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum FilePrefix {
+    #[value(name = "R")]
+    CoreRealtime,
+    #[value(name = "D")]
+    CoreDelayed,
+    #[value(name = "BR")]
+    BgcRealtime,
+    #[value(name = "BD")]
+    BgcDelayed,
+    #[value(name = "SR")]
+    SyntheticRealtime,
+    #[value(name = "SD")]
+    SyntheticDelayed,
+}
+
+impl FilePrefix {
+    // prefixes ingested when none are asked for explicitly
+    pub const DEFAULT: [FilePrefix; 6] = [
+        FilePrefix::CoreRealtime,
+        FilePrefix::CoreDelayed,
+        FilePrefix::BgcRealtime,
+        FilePrefix::BgcDelayed,
+        FilePrefix::SyntheticRealtime,
+        FilePrefix::SyntheticDelayed,
+    ];
+
+    // core files hold a cycle's pressure, temperature and salinity
+    pub fn is_core(self) -> bool {
+        matches!(self, FilePrefix::CoreRealtime | FilePrefix::CoreDelayed)
+    }
+
+    // B-files hold a cycle's BGC parameters; the core file alongside holds the rest
+    pub fn is_bgc(self) -> bool {
+        matches!(self, FilePrefix::BgcRealtime | FilePrefix::BgcDelayed)
+    }
+
+    // synthetic profiles merge core and BGC data onto one vertical axis, and are kept
+    // in collections of their own
+    pub fn is_synthetic(self) -> bool {
+        matches!(self, FilePrefix::SyntheticRealtime | FilePrefix::SyntheticDelayed)
+    }
+
+    fn parse(prefix: &str) -> Option<FilePrefix> {
+        match prefix {
+            "R" => Some(FilePrefix::CoreRealtime),
+            "D" => Some(FilePrefix::CoreDelayed),
+            "BR" => Some(FilePrefix::BgcRealtime),
+            "BD" => Some(FilePrefix::BgcDelayed),
+            "SR" => Some(FilePrefix::SyntheticRealtime),
+            "SD" => Some(FilePrefix::SyntheticDelayed),
+            _ => None,
+        }
+    }
+}
+
+// split a profile file name like BD5903629_098.nc into its prefix and WMO number
+pub fn parse_profile_name(name: &str) -> Option<(FilePrefix, u64)> {
+    let stem = name.strip_suffix(".nc")?;
+    let split = stem.find(|c: char| c.is_ascii_digit())?;
+    let prefix = FilePrefix::parse(&stem[..split])?;
+    let (wmo, _cycle) = stem[split..].split_once('_')?;
+    Some((prefix, wmo.parse().ok()?))
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct WalkFilter {
+    pub dacs: Vec<String>,
+    pub wmo_min: Option<u64>,
+    pub wmo_max: Option<u64>,
+    pub prefixes: Vec<FilePrefix>,
+}
+
+impl WalkFilter {
+    fn wmo_in_range(&self, wmo: u64) -> bool {
+        self.wmo_min.iter().all(|&min| wmo >= min) && self.wmo_max.iter().all(|&max| wmo <= max)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum SkipReason {
+    DacNotSelected,
+    NotAFloatDirectory,
+    NoProfilesDirectory,
+    WmoOutOfRange,
+    NotNetcdf,
+    UnrecognizedName,
+    PrefixNotSelected,
+}
+
+impl fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let reason = match self {
+            SkipReason::DacNotSelected => "DAC not selected",
+            SkipReason::NotAFloatDirectory => "not a float directory",
+            SkipReason::NoProfilesDirectory => "float directory has no profiles directory",
+            SkipReason::WmoOutOfRange => "WMO number outside requested range",
+            SkipReason::NotNetcdf => "not a netCDF file",
+            SkipReason::UnrecognizedName => "unrecognized profile file name",
+            SkipReason::PrefixNotSelected => "file prefix not selected",
+        };
+        write!(f, "{}", reason)
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct WalkResult {
+    pub files: Vec<String>,
+    pub skipped: Vec<(PathBuf, SkipReason)>,
+}
+
+// find every profile file under root that passes filter; whole DAC and float
+// directories that are filtered out are reported once rather than file by file
+pub fn walk_mirror(root: &Path, filter: &WalkFilter) -> io::Result<WalkResult> {
+    let prefixes: &[FilePrefix] = if filter.prefixes.is_empty() { &FilePrefix::DEFAULT } else { &filter.prefixes };
+    let mut result = WalkResult::default();
+
+    for dac_dir in sorted_subdirectories(root)? {
+        let dac = file_name(&dac_dir);
+        if !filter.dacs.is_empty() && !filter.dacs.contains(&dac) {
+            result.skipped.push((dac_dir, SkipReason::DacNotSelected));
+            continue;
+        }
+        for float_dir in sorted_subdirectories(&dac_dir)? {
+            match file_name(&float_dir).parse::<u64>() {
+                Ok(wmo) if !filter.wmo_in_range(wmo) => {
+                    result.skipped.push((float_dir, SkipReason::WmoOutOfRange));
+                    continue;
+                }
+                Ok(_) => {}
+                Err(_) => {
+                    result.skipped.push((float_dir, SkipReason::NotAFloatDirectory));
+                    continue;
+                }
+            }
+            let profiles_dir = float_dir.join("profiles");
+            if !profiles_dir.is_dir() {
+                result.skipped.push((float_dir, SkipReason::NoProfilesDirectory));
+                continue;
+            }
+            let mut entries: Vec<PathBuf> = fs::read_dir(&profiles_dir)?
+                .map(|entry| entry.map(|e| e.path()))
+                .collect::<io::Result<_>>()?;
+            entries.sort();
+            for path in entries {
+                if !path.is_file() {
+                    continue;
+                }
+                let name = file_name(&path);
+                if !name.ends_with(".nc") {
+                    result.skipped.push((path, SkipReason::NotNetcdf));
+                    continue;
+                }
+                match parse_profile_name(&name) {
+                    None => result.skipped.push((path, SkipReason::UnrecognizedName)),
+                    Some((_, wmo)) if !filter.wmo_in_range(wmo) => result.skipped.push((path, SkipReason::WmoOutOfRange)),
+                    Some((prefix, _)) if !prefixes.contains(&prefix) => result.skipped.push((path, SkipReason::PrefixNotSelected)),
+                    Some(_) => result.files.push(path.to_string_lossy().into_owned()),
+                }
+            }
+        }
+    }
+
+    Ok(result)
+}
+
+fn sorted_subdirectories(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut dirs = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            dirs.push(path);
+        }
+    }
+    dirs.sort();
+    Ok(dirs)
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn profile_names_split_into_prefix_and_wmo() {
+        assert_eq!(parse_profile_name("R1901727_357.nc"), Some((FilePrefix::CoreRealtime, 1901727)));
+        assert_eq!(parse_profile_name("BD5903629_098.nc"), Some((FilePrefix::BgcDelayed, 5903629)));
+        assert_eq!(parse_profile_name("SR5903629_001.nc"), Some((FilePrefix::SyntheticRealtime, 5903629)));
+        // descending profiles carry a D after the cycle number
+        assert_eq!(parse_profile_name("R1901727_357D.nc"), Some((FilePrefix::CoreRealtime, 1901727)));
+        assert_eq!(parse_profile_name("D1901727_001D.nc"), Some((FilePrefix::CoreDelayed, 1901727)));
+    }
+
+    #[test]
+    fn other_names_are_not_profiles() {
+        assert_eq!(parse_profile_name("1901727_prof.nc"), None);
+        assert_eq!(parse_profile_name("M1901727_357.nc"), None);
+        assert_eq!(parse_profile_name("R1901727_357.txt"), None);
+        assert_eq!(parse_profile_name("R1901727.nc"), None);
+        assert_eq!(parse_profile_name("Rabc_357.nc"), None);
+    }
+
+    #[test]
+    fn wmo_range_is_inclusive_and_open_ended() {
+        let unbounded = WalkFilter::default();
+        assert!(unbounded.wmo_in_range(0));
+        assert!(unbounded.wmo_in_range(u64::MAX));
+
+        let bounded = WalkFilter { wmo_min: Some(1900000), wmo_max: Some(1999999), ..Default::default() };
+        assert!(bounded.wmo_in_range(1900000));
+        assert!(bounded.wmo_in_range(1999999));
+        assert!(!bounded.wmo_in_range(1899999));
+        assert!(!bounded.wmo_in_range(2000000));
+
+        let from = WalkFilter { wmo_min: Some(5900000), ..Default::default() };
+        assert!(from.wmo_in_range(7900000));
+        assert!(!from.wmo_in_range(1901727));
+    }
+
+    #[test]
+    fn float_directories_without_profiles_are_reported() {
+        let root = TempDir::new().unwrap();
+        let root = root.path();
+        let profiles = root.join("aoml/1901727/profiles");
+        fs::create_dir_all(&profiles).unwrap();
+        fs::write(profiles.join("R1901727_357.nc"), b"").unwrap();
+        fs::write(profiles.join("R1901727_357.txt"), b"").unwrap();
+        fs::create_dir_all(root.join("aoml/1901728")).unwrap();
+        fs::create_dir_all(root.join("aoml/notes")).unwrap();
+
+        let result = walk_mirror(root, &WalkFilter::default()).unwrap();
+        assert_eq!(result.files, [profiles.join("R1901727_357.nc").to_string_lossy()]);
+        assert_eq!(result.skipped, [
+            (profiles.join("R1901727_357.txt"), SkipReason::NotNetcdf),
+            (root.join("aoml/1901728"), SkipReason::NoProfilesDirectory),
+            (root.join("aoml/notes"), SkipReason::NotAFloatDirectory),
+        ]);
+    }
+}
@@ -0,0 +1,171 @@
+// run temp copies of the devfiles through ingest into a MemorySink, to see what becomes of
+// each file without a database
+use nc2mongo::config::Config;
+use nc2mongo::file_list::FileEntry;
+use nc2mongo::ingest::{ingest, Documents};
+use nc2mongo::runs::RunSummary;
+use nc2mongo::sink::{MemorySink, Sink, WriteOutcome};
+use nc2mongo::{parse_profile_file, ProfileDoc};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tempfile::TempDir;
+
+const SOURCE_FILE: &str = "ftp://ftp.ifremer.fr/ifremer/argo/dac/aoml/1901727/profiles/R1901727_357.nc";
+
+// a mirror under the temp directory holding a copy of devfiles/R1901727_357.nc, laid out
+// so that the default config maps it to SOURCE_FILE
+struct Mirror {
+    root: TempDir,
+}
+
+impl Mirror {
+    fn new() -> Mirror {
+        let mirror = Mirror { root: TempDir::new().unwrap() };
+        fs::create_dir_all(mirror.profiles_dir()).unwrap();
+        let devfile = Path::new(env!("CARGO_MANIFEST_DIR")).join("../devfiles/R1901727_357.nc");
+        fs::copy(devfile, mirror.file()).unwrap();
+        mirror
+    }
+
+    fn profiles_dir(&self) -> PathBuf {
+        self.root.path().join("ifremer/aoml/1901727/profiles")
+    }
+
+    fn file(&self) -> PathBuf {
+        self.profiles_dir().join("R1901727_357.nc")
+    }
+
+    fn entry(&self) -> FileEntry {
+        FileEntry::listed(self.file().to_string_lossy())
+    }
+}
+
+async fn run(files: Vec<FileEntry>, sink: &Arc<MemorySink>) -> RunSummary {
+    let sink: Arc<dyn Sink> = sink.clone();
+    ingest(files, 2, Documents::Profiles, sink, None, &Config::default(), RunSummary::start("ingest")).await.unwrap()
+}
+
+#[tokio::test]
+async fn a_new_file_is_written() {
+    let mirror = Mirror::new();
+    let sink = Arc::new(MemorySink::new());
+    let summary = run(vec![mirror.entry()], &sink).await;
+    assert_eq!((summary.files, summary.unchanged, summary.stale, summary.deleted), (1, 0, 0, 0));
+    assert_eq!(summary.failure_count, 0);
+
+    let expected = parse_profile_file(&mirror.file()).unwrap();
+    let files = sink.files();
+    let stored = &files[SOURCE_FILE];
+    assert_eq!(stored.len(), expected.len());
+    assert_eq!(summary.profiles_written, expected.len());
+    for profile in stored {
+        assert_eq!(profile.data.source_file, SOURCE_FILE);
+        assert_eq!(profile.data.source_file_size, fs::metadata(mirror.file()).unwrap().len());
+    }
+}
+
+#[tokio::test]
+async fn a_file_with_the_stored_hash_is_unchanged() {
+    let mirror = Mirror::new();
+    let sink = Arc::new(MemorySink::new());
+    run(vec![mirror.entry()], &sink).await;
+    let written = sink.files();
+
+    let summary = run(vec![mirror.entry()], &sink).await;
+    assert_eq!((summary.unchanged, summary.stale, summary.deleted), (1, 0, 0));
+    assert_eq!(summary.failure_count, 0);
+    assert_eq!(sink.files()[SOURCE_FILE].len(), written[SOURCE_FILE].len());
+}
+
+#[tokio::test]
+async fn a_file_older_than_what_is_stored_is_stale() {
+    let mirror = Mirror::new();
+    let sink = Arc::new(MemorySink::new());
+    // what is stored came from other contents and was updated later than the file on disk
+    let mut newer: Vec<ProfileDoc> = parse_profile_file(&mirror.file()).unwrap();
+    for profile in &mut newer {
+        profile.data.source_file = SOURCE_FILE.to_string();
+        profile.data.source_file_sha256 = "not the file's hash".to_string();
+        profile.data.DATE_UPDATE = "99991231235959".to_string();
+    }
+    assert_eq!(sink.write_file(SOURCE_FILE, newer).await.unwrap(), WriteOutcome::Written);
+
+    let summary = run(vec![mirror.entry()], &sink).await;
+    assert_eq!((summary.unchanged, summary.stale, summary.deleted), (0, 1, 0));
+    assert_eq!(summary.failure_count, 0);
+    for profile in &sink.files()[SOURCE_FILE] {
+        assert_eq!(profile.data.DATE_UPDATE, "99991231235959");
+    }
+}
+
+#[tokio::test]
+async fn a_file_rsync_deleted_is_deleted() {
+    let mirror = Mirror::new();
+    let sink = Arc::new(MemorySink::new());
+    fs::remove_file(mirror.file()).unwrap();
+    fs::remove_dir_all(mirror.profiles_dir()).unwrap();
+    let entry = FileEntry { deleted: true, ..mirror.entry() };
+
+    let summary = run(vec![entry], &sink).await;
+    assert_eq!((summary.unchanged, summary.stale, summary.deleted), (0, 0, 1));
+    assert_eq!(summary.failure_count, 0);
+    assert_eq!(sink.deleted(), [SOURCE_FILE]);
+}
+
+#[tokio::test]
+async fn a_missing_file_in_its_profiles_directory_is_deleted() {
+    let mirror = Mirror::new();
+    let sink = Arc::new(MemorySink::new());
+    fs::remove_file(mirror.file()).unwrap();
+
+    let summary = run(vec![mirror.entry()], &sink).await;
+    assert_eq!(summary.deleted, 1);
+    assert_eq!(summary.failure_count, 0);
+    assert_eq!(sink.deleted(), [SOURCE_FILE]);
+}
+
+// the mirror is not mounted, or the path is wrong: nothing may be deleted
+#[tokio::test]
+async fn a_missing_file_without_its_directory_fails() {
+    let mirror = Mirror::new();
+    let sink = Arc::new(MemorySink::new());
+    fs::remove_dir_all(mirror.root.path().join("ifremer")).unwrap();
+
+    let summary = run(vec![mirror.entry()], &sink).await;
+    assert_eq!(summary.deleted, 0);
+    assert_eq!(summary.failure_count, 1);
+    assert_eq!(summary.failures[0].file, mirror.file().to_string_lossy());
+    assert!(sink.deleted().is_empty());
+}
+
+// writing no profiles is how the database sink deletes, and the memory sink agrees
+#[tokio::test]
+async fn writing_no_profiles_drops_the_file() {
+    let mirror = Mirror::new();
+    let sink = Arc::new(MemorySink::new());
+    run(vec![mirror.entry()], &sink).await;
+    assert_eq!(sink.write_file(SOURCE_FILE, Vec::new()).await.unwrap(), WriteOutcome::Written);
+    assert!(!sink.files().contains_key(SOURCE_FILE));
+}
+
+// documents from an older parser are replaced, even though neither the file nor its
+// DATE_UPDATE has changed
+#[tokio::test]
+async fn reprocessing_rewrites_unchanged_and_stale_files() {
+    let mirror = Mirror::new();
+    let sink = Arc::new(MemorySink::new().reprocessing());
+    run(vec![mirror.entry()], &sink).await;
+    let mut stored = sink.files()[SOURCE_FILE].clone();
+    for profile in &mut stored {
+        profile.data.DATA_CENTRE = "written by an older parser".to_string();
+    }
+    sink.write_file(SOURCE_FILE, stored).await.unwrap();
+
+    let summary = run(vec![mirror.entry()], &sink).await;
+    assert_eq!((summary.unchanged, summary.stale, summary.deleted), (0, 0, 0));
+    assert_eq!(summary.failure_count, 0);
+    for profile in &sink.files()[SOURCE_FILE] {
+        assert_ne!(profile.data.DATA_CENTRE, "written by an older parser");
+    }
+}
@@ -0,0 +1,179 @@
+// merge B-file profiles with core profiles. devfiles has no core file to go with its
+// B-files, so the core side is the B-file's profiles cut down to PRES, with a TEMP of its
+// own, or on disk a copy of the B-file standing in for its core file.
+use nc2mongo::config::Config;
+use nc2mongo::file_list::FileEntry;
+use nc2mongo::ingest::{ingest, Documents};
+use nc2mongo::merge::{bgc_entries, merge_profiles};
+use nc2mongo::runs::RunSummary;
+use nc2mongo::sink::{MemorySink, Sink};
+use nc2mongo::{parse_profile_file, Error, LevelValues, ProfileDoc};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tempfile::TempDir;
+
+fn bgc_profiles() -> Vec<ProfileDoc> {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("../devfiles/BD5903629_098.nc");
+    parse_profile_file(&path).unwrap()
+}
+
+fn core_profiles() -> Vec<ProfileDoc> {
+    let mut profiles = bgc_profiles();
+    for profile in &mut profiles {
+        let data = &mut profile.data;
+        data._id = data._id.replacen("BD", "D", 1);
+        data.source_file = data.source_file.replace("BD5903629_098.nc", "D5903629_098.nc");
+        data.STATION_PARAMETERS.retain(|param| param == "PRES");
+        for map in [&mut data.realtime_data, &mut data.adjusted_data].into_iter().flatten() {
+            map.retain(|param, _| param == "PRES");
+        }
+        if let Some(data_info) = &mut data.data_info {
+            data_info.retain(|param, _| param == "PRES");
+            let mut temp = data_info["PRES"].clone();
+            temp.UNITS = "degree_Celsius".to_string();
+            data_info.insert("TEMP".to_string(), temp);
+        }
+        let levels = data.realtime_data.as_ref().unwrap()["PRES"].len();
+        let temp = LevelValues::Scalar((0..levels).map(|level| 20.0 - level as f64 / 10.0).collect());
+        data.realtime_data.as_mut().unwrap().insert("TEMP".to_string(), temp);
+        data.STATION_PARAMETERS.push("TEMP".to_string());
+        data.JULD += 0.25;
+    }
+    profiles
+}
+
+#[test]
+fn merged_profiles_hold_every_parameter() {
+    let (core, bgc) = (core_profiles(), bgc_profiles());
+    let merged = merge_profiles(core.clone(), bgc.clone()).unwrap();
+    assert_eq!(merged.len(), bgc.len());
+    for (pfl, ((profile, bgc), core)) in merged.iter().zip(&bgc).zip(&core).enumerate() {
+        assert_eq!(profile.data._id, format!("5903629_098_{}", pfl));
+        assert_eq!(profile.search._id, profile.data._id);
+        assert_eq!(profile.data.source_file, bgc.data.source_file);
+        assert!(profile.data.core_source_file.as_deref().unwrap().ends_with("D5903629_098.nc"));
+        for param in bgc.data.STATION_PARAMETERS.iter().filter(|param| !param.is_empty()) {
+            assert!(profile.data.STATION_PARAMETERS.contains(param), "{} missing", param);
+        }
+        assert!(profile.data.STATION_PARAMETERS.contains(&"TEMP".to_string()));
+        assert_eq!(profile.search.STATION_PARAMETERS, profile.data.STATION_PARAMETERS);
+
+        // the B-file's parameters come from the B-file and the rest from the core file
+        let realtime_data = profile.data.realtime_data.as_ref().unwrap();
+        let data_info = profile.data.data_info.as_ref().unwrap();
+        for (param, values) in bgc.data.realtime_data.as_ref().unwrap() {
+            assert_eq!(&realtime_data[param], values, "{}", param);
+            assert!(data_info.contains_key(param), "{} has no data_info", param);
+        }
+        assert_eq!(realtime_data["TEMP"], core.data.realtime_data.as_ref().unwrap()["TEMP"]);
+        assert_eq!(data_info["TEMP"].UNITS, "degree_Celsius");
+        // as do the profile's time and position
+        assert_eq!(profile.data.JULD, core.data.JULD);
+        assert_ne!(profile.data.JULD, bgc.data.JULD);
+    }
+}
+
+#[test]
+fn differing_pres_is_a_conflict() {
+    let mut bgc = bgc_profiles();
+    if let Some(LevelValues::Scalar(pres)) = bgc[1].data.realtime_data.as_mut().unwrap().get_mut("PRES") {
+        pres[3] += 1.0;
+    }
+    match merge_profiles(core_profiles(), bgc) {
+        Err(Error::PresConflict { profile, reason }) => {
+            assert_eq!(profile, "D5903629_098_1");
+            assert!(reason.starts_with("level 3 "), "{}", reason);
+        }
+        other => panic!("expected a PRES conflict, got {:?}", other.map(|profiles| profiles.len())),
+    }
+}
+
+#[test]
+fn profiles_of_different_cycles_are_not_merged() {
+    let mut bgc = bgc_profiles();
+    bgc[0].data.CYCLE_NUMBER += 1;
+    assert!(matches!(merge_profiles(core_profiles(), bgc), Err(Error::Merge(_))));
+}
+
+// a mirror holding a B-file and, standing in for its core file, a copy of it
+struct Mirror {
+    root: TempDir,
+}
+
+impl Mirror {
+    fn new() -> Mirror {
+        let mirror = Mirror { root: TempDir::new().unwrap() };
+        fs::create_dir_all(mirror.profiles_dir()).unwrap();
+        let devfile = Path::new(env!("CARGO_MANIFEST_DIR")).join("../devfiles/BD5903629_098.nc");
+        fs::copy(&devfile, mirror.file("BD5903629_098.nc")).unwrap();
+        fs::copy(&devfile, mirror.file("R5903629_098.nc")).unwrap();
+        mirror
+    }
+
+    fn profiles_dir(&self) -> PathBuf {
+        self.root.path().join("ifremer/csiro/5903629/profiles")
+    }
+
+    fn file(&self, name: &str) -> PathBuf {
+        self.profiles_dir().join(name)
+    }
+
+    fn entries(&self, name: &str) -> Vec<FileEntry> {
+        bgc_entries(vec![FileEntry::listed(self.file(name).to_string_lossy())])
+    }
+}
+
+const URL: &str = "ftp://ftp.ifremer.fr/ifremer/argo/dac/csiro/5903629/profiles/";
+
+async fn merge(files: Vec<FileEntry>, sink: &Arc<MemorySink>) -> RunSummary {
+    let sink: Arc<dyn Sink> = sink.clone();
+    ingest(files, 2, Documents::Merged, sink, None, &Config::default(), RunSummary::start("merge")).await.unwrap()
+}
+
+// a core file in the list refreshes the merged documents of the B-file alongside it
+#[tokio::test]
+async fn core_files_refresh_the_merged_view() {
+    let mirror = Mirror::new();
+    let sink = Arc::new(MemorySink::new());
+    let summary = merge(mirror.entries("R5903629_098.nc"), &sink).await;
+    assert_eq!((summary.files, summary.failure_count), (1, 0));
+    let files = sink.files();
+    let merged = &files[&format!("{}BD5903629_098.nc", URL)];
+    assert_eq!(merged.len(), 2);
+    for (pfl, profile) in merged.iter().enumerate() {
+        assert_eq!(profile.data._id, format!("5903629_098_{}", pfl));
+        assert_eq!(profile.data.core_source_file.as_deref(), Some(format!("{}R5903629_098.nc", URL).as_str()));
+    }
+
+    // nothing changed, so nothing to do
+    let summary = merge(mirror.entries("BD5903629_098.nc"), &sink).await;
+    assert_eq!((summary.unchanged, summary.failure_count), (1, 0));
+
+    // the core file moves to delayed mode; its second profile has its own time and position
+    let fixture = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/BD5903629_098.nc");
+    fs::copy(fixture, mirror.file("D5903629_098.nc")).unwrap();
+    fs::remove_file(mirror.file("R5903629_098.nc")).unwrap();
+    let entries = bgc_entries(vec![
+        FileEntry { path: mirror.file("R5903629_098.nc").to_string_lossy().into_owned(), deleted: true },
+        FileEntry::listed(mirror.file("D5903629_098.nc").to_string_lossy()),
+    ]);
+    assert_eq!(entries, mirror.entries("BD5903629_098.nc"));
+    let sink = Arc::new(MemorySink::new());
+    let summary = merge(entries, &sink).await;
+    assert_eq!(summary.failure_count, 0);
+    let merged = &sink.files()[&format!("{}BD5903629_098.nc", URL)];
+    assert_eq!(merged[1].data.core_source_file.as_deref(), Some(format!("{}D5903629_098.nc", URL).as_str()));
+    assert_eq!(merged[1].data.JULD, 22297.8125);
+}
+
+#[tokio::test]
+async fn a_b_file_without_a_core_file_fails() {
+    let mirror = Mirror::new();
+    fs::remove_file(mirror.file("R5903629_098.nc")).unwrap();
+    let sink = Arc::new(MemorySink::new());
+    let summary = merge(mirror.entries("BD5903629_098.nc"), &sink).await;
+    assert_eq!(summary.failure_count, 1);
+    assert!(summary.failures[0].reason.starts_with("cannot merge: no core file"), "{}", summary.failures[0].reason);
+    assert!(sink.files().is_empty());
+}
@@ -0,0 +1,177 @@
+// decode the sample files in devfiles, and fixtures derived from them, end to end
+use nc2mongo::{parse_profile_file, DataSchema, Error, LevelValues, ProfileDoc, SamplingScheme};
+use std::path::{Path, PathBuf};
+
+fn devfile(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("../devfiles").join(name)
+}
+
+fn fixture(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(name)
+}
+
+fn parse(path: &Path) -> Vec<ProfileDoc> {
+    parse_profile_file(path).unwrap_or_else(|e| panic!("failed to parse {}: {}", path.display(), e))
+}
+
+// what the file itself holds for one profile, read straight from the netCDF variables
+fn scalar<T: netcdf::NcPutGet>(file: &netcdf::File, name: &str, pfl: usize) -> T {
+    file.variable(name).unwrap().get_value([pfl]).unwrap()
+}
+
+#[test]
+fn devfiles_read_scalars_at_each_profile_index() {
+    for name in ["BD5903629_098.nc", "BD5903629_099.nc", "R1901727_357.nc", "R1901727_358.nc"] {
+        let path = devfile(name);
+        let file = netcdf::open(&path).unwrap();
+        let profiles = parse(&path);
+        assert_eq!(profiles.len(), file.dimension("N_PROF").unwrap().len(), "{}", name);
+        for (pfl, profile) in profiles.iter().enumerate() {
+            let data = &profile.data;
+            assert_eq!(data._id, format!("{}_{}", name.trim_end_matches(".nc"), pfl));
+            assert_eq!(data.CYCLE_NUMBER, scalar::<i32>(&file, "CYCLE_NUMBER", pfl), "{} profile {}", name, pfl);
+            assert_eq!(data.JULD, scalar::<f64>(&file, "JULD", pfl), "{} profile {}", name, pfl);
+            assert_eq!(data.JULD_LOCATION, scalar::<f64>(&file, "JULD_LOCATION", pfl), "{} profile {}", name, pfl);
+            assert_eq!(data.geolocation.coordinates, [scalar::<f64>(&file, "LONGITUDE", pfl), scalar::<f64>(&file, "LATITUDE", pfl)], "{} profile {}", name, pfl);
+            assert_eq!(data.CONFIG_MISSION_NUMBER, scalar::<i32>(&file, "CONFIG_MISSION_NUMBER", pfl), "{} profile {}", name, pfl);
+        }
+    }
+}
+
+// tests/fixtures/BD5903629_098.nc is devfiles/BD5903629_098.nc with the second profile's
+// JULD, JULD_LOCATION, LATITUDE, LONGITUDE and CONFIG_MISSION_NUMBER changed, so that
+// reading the first profile's values for both shows up
+#[test]
+fn secondary_profile_keeps_its_own_time_and_position() {
+    let profiles = parse(&fixture("BD5903629_098.nc"));
+    assert_eq!(profiles.len(), 2);
+    let (primary, secondary) = (&profiles[0], &profiles[1]);
+
+    assert_eq!(primary.data.CYCLE_NUMBER, 98);
+    assert_eq!(primary.data.JULD, 22297.7840162036);
+    assert_eq!(primary.data.JULD_LOCATION, 22297.793634259142);
+    assert_eq!(primary.data.geolocation.coordinates, [138.675, -44.247]);
+    assert_eq!(primary.data.CONFIG_MISSION_NUMBER, 1);
+
+    assert_eq!(secondary.data.CYCLE_NUMBER, 98);
+    assert_eq!(secondary.data.JULD, 22297.8125);
+    assert_eq!(secondary.data.JULD_LOCATION, 22297.82);
+    assert_eq!(secondary.data.geolocation.coordinates, [138.712, -44.301]);
+    assert_eq!(secondary.data.CONFIG_MISSION_NUMBER, 2);
+
+    // argo_search gets the same per-profile time and position
+    for profile in &profiles {
+        assert_eq!(profile.search._id, profile.data._id);
+        assert_eq!(profile.search.JULD, profile.data.JULD);
+        assert_eq!(profile.search.geolocation.coordinates, profile.data.geolocation.coordinates);
+    }
+}
+
+// tests/fixtures/R1901727_357_no_units.nc is devfiles/R1901727_357.nc with TEMP's units
+// attribute renamed to unitz; the file fails rather than losing its data_info
+#[test]
+fn a_parameter_without_units_fails_the_file() {
+    match parse_profile_file(&fixture("R1901727_357_no_units.nc")) {
+        Err(error @ Error::MissingAttribute { .. }) => {
+            assert_eq!(error.category(), "missing_attribute");
+            assert_eq!(error.to_string(), "missing attribute units on TEMP");
+        }
+        other => panic!("expected a missing attribute, got {:?}", other.map(|profiles| profiles.len())),
+    }
+}
+
+// tests/fixtures/R1901727_357_bad_parameter_name.nc is devfiles/R1901727_357.nc with the E of
+// TEMP in STATION_PARAMETERS replaced by 0xff
+#[test]
+fn a_parameter_name_that_is_not_utf8_fails_the_file() {
+    match parse_profile_file(&fixture("R1901727_357_bad_parameter_name.nc")) {
+        Err(error @ Error::StringDecode { .. }) => {
+            assert_eq!(error.category(), "string_decode");
+            assert!(error.to_string().starts_with("could not decode STATION_PARAMETERS: invalid utf-8"));
+        }
+        other => panic!("expected a string decode error, got {:?}", other.map(|profiles| profiles.len())),
+    }
+}
+
+#[test]
+fn per_profile_strings_follow_the_profile_index() {
+    let profiles = parse(&devfile("BD5903629_098.nc"));
+    assert!(profiles[0].data.VERTICAL_SAMPLING_SCHEME.starts_with("Primary sampling"));
+    assert!(profiles[1].data.VERTICAL_SAMPLING_SCHEME.starts_with("Secondary sampling"));
+}
+
+#[test]
+fn profiles_are_classified_by_sampling_scheme() {
+    let profiles = parse(&devfile("BD5903629_098.nc"));
+    assert_eq!(profiles[0].data.sampling_scheme, SamplingScheme::Primary);
+    assert_eq!(profiles[1].data.sampling_scheme, SamplingScheme::Secondary);
+    for profile in &profiles {
+        assert_eq!(profile.search.sampling_scheme, profile.data.sampling_scheme);
+    }
+    assert_eq!(parse(&devfile("R1901727_357.nc"))[0].search.sampling_scheme, SamplingScheme::Primary);
+}
+
+// parameters with one value per level keep their flat arrays; only those with an
+// N_VALUES dimension are nested
+#[test]
+fn single_valued_parameters_stay_flat() {
+    let profiles = parse(&devfile("BD5903629_098.nc"));
+    let document = serde_json::to_value(&profiles[0].data).unwrap();
+    let realtime_data = document["realtime_data"].as_object().unwrap();
+    assert!(!realtime_data.is_empty());
+    for (param, values) in realtime_data {
+        assert!(values.as_array().unwrap().iter().all(|value| value.is_number()), "{} is nested", param);
+    }
+    for (param, info) in profiles[0].data.data_info.as_ref().unwrap() {
+        assert_eq!(info.N_VALUES, None, "{}", param);
+    }
+}
+
+// tests/fixtures/BD5903629_098_nvalues.nc is devfiles/BD5903629_098.nc with a
+// UV_INTENSITY_NITRATE [N_PROF, N_LEVELS, N_VALUES4] variable taking the place of the second
+// profile's TEMP_CPU_CHLA. That profile has intensities at its first five levels, one of
+// them missing at the third level, and fill below; its other parameters have 62 levels.
+#[test]
+fn parameters_with_n_values_get_an_array_per_level() {
+    let profiles = parse(&fixture("BD5903629_098_nvalues.nc"));
+    let data = &profiles[1].data;
+    let realtime_data = data.realtime_data.as_ref().unwrap();
+    let pres_levels = realtime_data["PRES"].len();
+    assert_eq!(pres_levels, 62);
+
+    // trailing fill levels are cut, then the array is padded back out to the profile's
+    // levels with arrays of fill
+    let intensity = match &realtime_data["UV_INTENSITY_NITRATE"] {
+        LevelValues::Array(levels) => levels,
+        other => panic!("expected an array per level, got {:?}", other),
+    };
+    assert_eq!(intensity.len(), pres_levels);
+    assert_eq!(intensity[0], [1000.0, 1001.0, 1002.0, 1003.0]);
+    assert_eq!(intensity[2], [1020.0, 99999.0, 1022.0, 1023.0]);
+    assert_eq!(intensity[4], [1040.0, 1041.0, 1042.0, 1043.0]);
+    for level in &intensity[5..] {
+        assert_eq!(level, &[99999.0; 4]);
+    }
+    for (param, values) in realtime_data.iter().filter(|(param, _)| *param != "UV_INTENSITY_NITRATE") {
+        assert!(matches!(values, LevelValues::Scalar(_)), "{} is nested", param);
+        assert_eq!(values.len(), pres_levels, "{}", param);
+    }
+
+    let data_info = data.data_info.as_ref().unwrap();
+    let info = &data_info["UV_INTENSITY_NITRATE"];
+    assert_eq!(info.VALUES_DIMENSION.as_deref(), Some("N_VALUES4"));
+    assert_eq!(info.N_VALUES, Some(4));
+    assert_eq!(info.UNITS, "count");
+    assert_eq!(info.DATA_MODE, "R");
+    assert_eq!(data_info["PRES"].N_VALUES, None);
+
+    // stored as nested arrays, and read back the same way
+    let document = serde_json::to_value(data).unwrap();
+    assert_eq!(document["realtime_data"]["UV_INTENSITY_NITRATE"][2], serde_json::json!([1020.0, 99999.0, 1022.0, 1023.0]));
+    assert_eq!(document["data_info"]["UV_INTENSITY_NITRATE"]["N_VALUES"], 4);
+    let read_back: DataSchema = serde_json::from_value(document).unwrap();
+    assert_eq!(read_back.realtime_data.unwrap()["UV_INTENSITY_NITRATE"], realtime_data["UV_INTENSITY_NITRATE"]);
+
+    // the first profile doesn't list it
+    assert!(!profiles[0].data.realtime_data.as_ref().unwrap().contains_key("UV_INTENSITY_NITRATE"));
+}